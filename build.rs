@@ -13,10 +13,7 @@ macro_rules! p {
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    prost_build::compile_protos(
-        &["src/proto/message.proto", "src/proto/stpw.proto"],
-        &["src/proto/"],
-    )?;
+    prost_build::compile_protos(&["src/proto/stpw.proto"], &["src/proto/"])?;
     embuild::build::CfgArgs::output_propagated("ESP_IDF")?;
     embuild::build::LinkArgs::output_propagated("ESP_IDF")?;
     build_env()?;
@@ -57,7 +54,36 @@ fn build_env() -> Result<(), Box<dyn std::error::Error>> {
         "DEPHY_ENDPOINT_HTTP",
         "https://send.testnet.dephy.io/dephy/signed_message"
     );
+    env_string!("NOSTR_RELAY_URL", "");
+    env_number!("NOSTR_EVENT_KIND", u16, 1);
+    env_number!("APP_HTTP_SERVER_PORT", u16, 80);
+    env_string!("DID_SERVICES", "");
+    env_string!("VC_ENDPOINT_HTTP", "");
+    env_string!("COSE_ENDPOINT_HTTP", "");
+    env_number!("APP_HTTP_TOKEN_LIFETIME", u64, 60);
+    env_string!("APP_HTTP_TOKEN_AUDIENCE", "");
+    env_string!("SIWE_NONCE_URL", "");
+    env_string!("SIWE_LOGIN_URL", "");
+    env_string!("SIWE_STATEMENT", "");
+    env_number!("SIWE_CHAIN_ID", u64, 1);
+    env_number!("SIWE_SESSION_LIFETIME", u64, 3600);
     env_number!("APP_SEND_LOOP_DURATION", u64, 10);
+    env_string!("APP_ENCRYPT_TO_PUBKEY", "");
+    env_number!("APP_NONCE_BATCH_SIZE", u64, 100);
+    env_number!("APP_NONCE_IS_TIMESTAMP", bool, false);
+    env_number!("APP_MAX_CLOCK_SKEW", u64, 300);
+    env_string!("APP_SIGNATURE_SCHEME", "SS_ECDSA_RECOVERABLE");
+    env_number!("APP_SESSION_KEY_LIFETIME", u64, 0);
+    env_string!("W3B_TOPIC", "");
+    env_string!("W3B_TOKEN", "");
+    env_string!("W3B_ENCODING", "WPE_UTF8");
+    env_number!("ENTROPY_MAX_WAIT_SECS", u64, 3600);
+    env_number!("ENTROPY_MIN_WAIT_SECS", u64, 60);
+    env_number!("ENTROPY_MIN_SAMPLES", u64, 262144);
+    env_number!("ENTROPY_MIN_BITS", u8, 4);
+    env_number!("EFUSE_KEY_BLOCK", usize, 4);
+    env_string!("EFUSE_EXTRA_IDENTITIES", "");
+    env_string!("EFUSE_RESERVED_BLOCKS", "");
 
     for l in lines.iter() {
         p!("cargo:warning={}", l)
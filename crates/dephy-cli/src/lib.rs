@@ -0,0 +1,286 @@
+//! Commands of `dephy-cli`, kept out of `main.rs` so that they can be tested.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, ValueEnum};
+use dephy_message::*;
+use k256::PublicKey;
+use prost::Message;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+#[derive(Args)]
+pub struct Input {
+    /// Hex-encoded message, `@<path>` to read from a file, or `-` to read from stdin
+    message: String,
+
+    /// How the message is encoded, `auto` accepts both hex and binary
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    format: InputFormat,
+}
+
+#[derive(Copy, Clone, PartialEq, ValueEnum)]
+pub enum InputFormat {
+    Auto,
+    Hex,
+    Bin,
+}
+
+#[derive(Args)]
+pub struct SignArgs {
+    /// Hex-encoded secret key to sign with
+    #[arg(long)]
+    key: String,
+
+    /// Payload as UTF-8 text
+    #[arg(long, conflicts_with = "payload_hex")]
+    payload: Option<String>,
+
+    /// Payload as hex
+    #[arg(long)]
+    payload_hex: Option<String>,
+
+    /// Recipient address, defaults to the recipient of `--encrypt-to` or all zeros
+    #[arg(long)]
+    to: Option<String>,
+
+    /// Hex-encoded SEC1 public key of the recipient to encrypt the payload to
+    #[arg(long)]
+    encrypt_to: Option<String>,
+
+    /// Defaults to now
+    #[arg(long)]
+    timestamp: Option<u64>,
+
+    /// Defaults to the timestamp
+    #[arg(long)]
+    nonce: Option<u64>,
+
+    /// Address of the last edge node that relayed the message
+    #[arg(long)]
+    last_edge_addr: Option<String>,
+
+    /// W3bstream topic, enables W3bstream routing
+    #[arg(long, requires = "w3b_token")]
+    w3b_topic: Option<String>,
+
+    #[arg(long)]
+    w3b_token: Option<String>,
+
+    /// One of `WPE_UTF8`, `WPE_UTF8_JSON`, `WPE_HEX` and `WPE_BASE64`
+    #[arg(long, default_value = "WPE_UTF8")]
+    w3b_encoding: String,
+
+    /// Sign with BIP-340 Schnorr instead of recoverable ECDSA
+    #[arg(long)]
+    schnorr: bool,
+
+    /// Encode as a COSE_Sign1 message signed with ES256K instead of a SignedMessage
+    #[arg(long, conflicts_with_all = ["schnorr", "w3b_topic", "last_edge_addr"])]
+    cose: bool,
+
+    /// Write the binary message to a file instead of printing it in hex
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+/// Signs a test message, encoded as a `SignedMessage` or a COSE_Sign1 message.
+pub fn sign(args: SignArgs) -> Result<Vec<u8>> {
+    let signer = SoftwareSigner::from_slice(&decode_hex(&args.key)?)?;
+    let timestamp = match args.timestamp {
+        Some(t) => t,
+        None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    let payload = match (args.payload, args.payload_hex) {
+        (Some(p), _) => p.into_bytes(),
+        (_, Some(p)) => decode_hex(&p)?,
+        _ => vec![],
+    };
+
+    let mut builder = MessageBuilder::new()
+        .timestamp(timestamp)
+        .nonce(args.nonce.unwrap_or(timestamp))
+        .payload(payload);
+    if let Some(to) = args.to {
+        builder = builder.to_address(decode_hex(&to)?);
+    }
+    if let Some(peer) = args.encrypt_to {
+        let peer = PublicKey::from_sec1_bytes(&decode_hex(&peer)?)
+            .map_err(|_| anyhow!("Invalid public key to encrypt to!"))?;
+        builder = builder.encrypt_to(peer, rand::random());
+    }
+    if let Some(addr) = args.last_edge_addr {
+        builder = builder.last_edge_addr(decode_hex(&addr)?);
+    }
+    if let Some(topic) = args.w3b_topic {
+        let encoding = W3bstreamPayloadEncoding::from_str_name(&args.w3b_encoding).ok_or(
+            anyhow!("Unknown W3bstream payload encoding: {}", args.w3b_encoding),
+        )?;
+        builder = builder.w3b(W3bstreamOptions {
+            topic,
+            token: args.w3b_token.unwrap_or_default(),
+            encoding: encoding.into(),
+        });
+    }
+
+    if args.schnorr {
+        builder = builder.schnorr(rand::random());
+    }
+
+    if args.cose {
+        builder.build_cose(&signer)
+    } else {
+        Ok(builder.build(&signer)?.encode_to_vec())
+    }
+}
+
+/// Runs the full verification of a `SignedMessage` or a COSE_Sign1 message and prints it,
+/// returns the message and its payload, decrypted with `key` if it was encrypted.
+pub fn verify(data: &[u8], key: Option<&str>) -> Result<(RawMessage, Vec<u8>)> {
+    let signer = key
+        .map(|key| SoftwareSigner::from_slice(&decode_hex(key)?))
+        .transpose()?;
+    let (raw, payload) = if is_cose_sign1(data) {
+        let (raw, nonce, payload) = if let Some(signer) = &signer {
+            check_and_decrypt_cose_message(data, signer)?
+        } else {
+            let (raw, nonce) = check_cose_message(data)?;
+            let payload = raw.payload.clone();
+            (raw, nonce, payload)
+        };
+        println!("COSE_Sign1");
+        println!("  nonce:          {}", nonce);
+        (raw, payload)
+    } else {
+        let (msg, raw, payload) = if let Some(signer) = &signer {
+            // Replays and clock skew are not checked for a single message.
+            MessageChecker::new(MemoryReplayGuard::new(), u64::MAX)
+                .delegation(DelegationMode::Follow)
+                .check_and_decrypt(data, 0, signer)?
+        } else {
+            let (msg, raw) = check_delegated_message(data)?;
+            let payload = raw.payload.clone();
+            (msg, raw, payload)
+        };
+        print_message(&msg);
+        (raw, payload)
+    };
+    print_raw_message(&raw);
+    if raw.encrypted && payload != raw.payload {
+        println!("Decrypted payload: {}", format_bytes(&payload));
+    }
+    println!("Verification: OK");
+    Ok((raw, payload))
+}
+
+/// Decodes a `SignedMessage` or a COSE_Sign1 message without verifying it and prints it.
+pub fn decode(data: &[u8]) -> Result<RawMessage> {
+    let raw = if is_cose_sign1(data) {
+        let (raw, nonce) = decode_cose_message(data)?;
+        println!("COSE_Sign1");
+        println!("  nonce:          {}", nonce);
+        raw
+    } else {
+        let msg = SignedMessage::decode(data)?;
+        let raw = RawMessage::decode(msg.raw.as_slice())?;
+        print_message(&msg);
+        raw
+    };
+    print_raw_message(&raw);
+    Ok(raw)
+}
+
+pub fn read_input(input: &Input) -> Result<Vec<u8>> {
+    let data = if input.message == "-" {
+        let mut buf = vec![];
+        io::stdin().read_to_end(&mut buf)?;
+        buf
+    } else if let Some(path) = input.message.strip_prefix('@') {
+        fs::read(path).with_context(|| format!("Failed to read {}", path))?
+    } else if input.format == InputFormat::Bin {
+        bail!("Binary messages must be read from a file or stdin.")
+    } else {
+        input.message.clone().into_bytes()
+    };
+
+    match input.format {
+        InputFormat::Bin => Ok(data),
+        InputFormat::Hex => decode_hex(std::str::from_utf8(&data)?),
+        InputFormat::Auto => match std::str::from_utf8(&data) {
+            Ok(s) if is_hex(s) => decode_hex(s),
+            _ => Ok(data),
+        },
+    }
+}
+
+fn is_hex(s: &str) -> bool {
+    let s = s.trim();
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    !s.is_empty() && s.len() % 2 == 0 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+pub fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    let s = s.trim();
+    Ok(hex::decode(s.strip_prefix("0x").unwrap_or(s))?)
+}
+
+fn format_bytes(buf: &[u8]) -> String {
+    match std::str::from_utf8(buf) {
+        Ok(s) if !s.chars().any(|c| c.is_control()) => format!("{:?}", s),
+        _ => format!("0x{}", hex::encode(buf)),
+    }
+}
+
+fn print_message(msg: &SignedMessage) {
+    println!("SignedMessage");
+    println!("  hash:           0x{}", hex::encode(&msg.hash));
+    println!("  nonce:          {}", msg.nonce);
+    println!("  signature:      0x{}", hex::encode(&msg.signature));
+    println!("  scheme:         {}", msg.signature_scheme().as_str_name());
+    if let Some(public_key) = &msg.public_key {
+        println!("  public_key:     0x{}", hex::encode(public_key));
+    }
+    if let Some(cert) = &msg.delegation {
+        match DelegationRaw::decode(cert.raw.as_slice()) {
+            Ok(d) => {
+                println!(
+                    "  delegation:     session_public_key=0x{} not_before={} not_after={}",
+                    hex::encode(&d.session_public_key),
+                    d.not_before,
+                    d.not_after
+                );
+            }
+            Err(e) => println!("  delegation:     {}", e),
+        }
+    }
+    println!(
+        "  last_edge_addr: {}",
+        match &msg.last_edge_addr {
+            Some(addr) => format!("0x{}", hex::encode(addr)),
+            None => "None".to_string(),
+        }
+    );
+}
+
+fn print_raw_message(raw: &RawMessage) {
+    println!("RawMessage");
+    println!("  timestamp:      {}", raw.timestamp);
+    println!("  from_address:   0x{}", hex::encode(&raw.from_address));
+    println!("  to_address:     0x{}", hex::encode(&raw.to_address));
+    println!("  encrypted:      {}", raw.encrypted);
+    println!(
+        "  iv:             {}",
+        match &raw.iv {
+            Some(iv) => format!("0x{}", hex::encode(iv)),
+            None => "None".to_string(),
+        }
+    );
+    if let Some(w3b) = &raw.w3b {
+        println!("  w3b.topic:      {}", w3b.topic);
+        println!("  w3b.token:      {}", w3b.token);
+        println!("  w3b.encoding:   {}", w3b.encoding().as_str_name());
+    } else {
+        println!("  w3b:            None");
+    }
+    println!("  payload:        {}", format_bytes(&raw.payload));
+}
@@ -0,0 +1,119 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use dephy_cli::*;
+use dephy_message::*;
+use k256::SecretKey;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Signs, decodes and verifies DePHY messages on a workstation.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Print debug logs, including the details of signature verification
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decode a SignedMessage or a COSE_Sign1 message and run the full verification
+    Verify {
+        #[command(flatten)]
+        input: Input,
+
+        /// Hex-encoded secret key of the recipient, to decrypt encrypted payloads
+        #[arg(long)]
+        key: Option<String>,
+    },
+    /// Decode a SignedMessage or a COSE_Sign1 message without verifying it
+    Decode {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Sign a test message with a software key
+    Sign(Box<SignArgs>),
+    /// Verify a JWT-VC issued by a `did:dephy` identity and print its claims
+    VerifyVc {
+        /// Compact JWT of the credential
+        token: String,
+    },
+    /// Generate a random software key
+    Keygen,
+    /// Derive the key for a purpose from a root key, the same way devices do
+    Derive {
+        /// Hex-encoded root secret key
+        #[arg(long)]
+        key: String,
+
+        /// One of `dephy:signing`, `dephy:encryption`, `dephy:ble-pairing` and
+        /// `dephy:transport-auth`, or a custom label
+        #[arg(long)]
+        purpose: String,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    env_logger::Builder::new()
+        .filter_level(if cli.verbose {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Info
+        })
+        .parse_default_env()
+        .init();
+
+    match cli.command {
+        Command::Verify { input, key } => {
+            verify(&read_input(&input)?, key.as_deref())?;
+        }
+        Command::Decode { input } => {
+            decode(&read_input(&input)?)?;
+        }
+        Command::Sign(args) => {
+            let output = args.output.clone();
+            let msg = sign(*args)?;
+            if let Some(path) = output {
+                fs::write(&path, msg).with_context(|| format!("Failed to write {}", path))?;
+            } else {
+                println!("{}", hex::encode(msg));
+            }
+        }
+        Command::VerifyVc { token } => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let (issuer, claims) = check_credential(&token, now)?;
+            println!("Issuer: {}", addr_bytes_to_did_str(&issuer));
+            println!("{}", serde_json::to_string_pretty(&claims)?);
+            println!("Verification: OK");
+        }
+        Command::Keygen => {
+            let key = SecretKey::random(&mut rand::thread_rng());
+            println!("secret_key: 0x{}", hex::encode(key.to_bytes()));
+            println!(
+                "public_key: 0x{}",
+                hex::encode(key.public_key().to_sec1_bytes())
+            );
+            println!("address:    {}", get_eth_address(&key.public_key().into()));
+        }
+        Command::Derive { key, purpose } => {
+            let root = SecretKey::from_slice(&decode_hex(&key)?)
+                .map_err(|_| anyhow!("Invalid secret key!"))?;
+            let key = match KeyPurpose::from_label(&purpose) {
+                Some(purpose) => derive_key(&root, purpose)?,
+                None => derive_key_with_label(&root, &purpose)?,
+            };
+            println!("secret_key: 0x{}", hex::encode(key.to_bytes()));
+            println!(
+                "public_key: 0x{}",
+                hex::encode(key.public_key().to_sec1_bytes())
+            );
+            println!("address:    {}", get_eth_address(&key.public_key().into()));
+        }
+    }
+
+    Ok(())
+}
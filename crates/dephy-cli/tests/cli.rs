@@ -0,0 +1,57 @@
+use clap::Parser;
+use dephy_cli::*;
+use k256::SecretKey;
+
+// Private key from the web3.js documentation, the address is well known.
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+const PEER_KEY_HEX: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+/// `sign` arguments parsed the way the command line does.
+#[derive(Parser)]
+struct SignCli {
+    #[command(flatten)]
+    args: SignArgs,
+}
+
+fn sign_with(args: &[&str]) -> Vec<u8> {
+    let args = ["sign", "--key", KEY_HEX].iter().chain(args);
+    sign(SignCli::parse_from(args).args).unwrap()
+}
+
+#[test]
+fn sign_verify_decode_round_trip() {
+    let msg = sign_with(&["--payload", "hello", "--timestamp", "1700000000"]);
+
+    let (raw, payload) = verify(&msg, None).unwrap();
+    assert_eq!(hex::encode(&raw.from_address), ADDR_HEX);
+    assert_eq!(raw.timestamp, 1700000000);
+    assert_eq!(payload, b"hello");
+
+    assert_eq!(decode(&msg).unwrap(), raw);
+
+    let mut tampered = msg.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(verify(&tampered, None).is_err());
+}
+
+#[test]
+fn sign_verify_encrypted_and_cose() {
+    let peer = SecretKey::from_slice(&hex::decode(PEER_KEY_HEX).unwrap()).unwrap();
+    let peer_pubkey = hex::encode(peer.public_key().to_sec1_bytes());
+
+    let msg = sign_with(&["--payload", "secret", "--encrypt-to", &peer_pubkey]);
+    let (raw, payload) = verify(&msg, Some(PEER_KEY_HEX)).unwrap();
+    assert!(raw.encrypted);
+    assert_ne!(raw.payload, b"secret");
+    assert_eq!(payload, b"secret");
+    assert!(verify(&msg, Some(KEY_HEX)).is_err());
+
+    let msg = sign_with(&["--payload", "hello", "--cose"]);
+    let (raw, payload) = verify(&msg, None).unwrap();
+    assert_eq!(hex::encode(&raw.from_address), ADDR_HEX);
+    assert_eq!(payload, b"hello");
+    assert_eq!(decode(&msg).unwrap(), raw);
+}
@@ -0,0 +1,165 @@
+//! Local stand-in of the DePHY ingest service, kept out of `main.rs` so that it can be
+//! tested.
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use dephy_message::*;
+use log::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tiny_http::{Method, Request};
+
+static CONTENT_TYPE_DEPHY: &str = "application/x-dephy";
+static MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// Accepted messages and the last nonce seen from every sender.
+pub struct Ingest {
+    store: File,
+    checker: MessageChecker<MemoryReplayGuard>,
+    max_clock_skew: u64,
+    /// Nonces of bearer tokens seen, until they expire.
+    token_nonces: HashMap<String, u64>,
+}
+
+impl Ingest {
+    pub fn open(path: &Path, max_clock_skew: u64) -> Result<Self> {
+        let mut checker = MessageChecker::new(MemoryReplayGuard::new(), max_clock_skew)
+            .delegation(DelegationMode::Follow);
+        if path.exists() {
+            // Replays are checked against messages accepted before restarting as well.
+            let guard = checker.guard_mut();
+            let reader = BufReader::new(File::open(path)?);
+            for line in reader.lines() {
+                let data = hex::decode(line?.trim())?;
+                let (msg, raw) = check_delegated_message(&data)?;
+                let last = guard.last_nonce(&raw.from_address).unwrap_or_default();
+                guard.accept(&raw.from_address, msg.nonce.max(last))?;
+            }
+            info!(
+                "Loaded messages from {} senders in {}",
+                guard.len(),
+                path.display()
+            );
+        }
+        let store = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self {
+            store,
+            checker,
+            max_clock_skew,
+            token_nonces: HashMap::new(),
+        })
+    }
+
+    /// Checks a bearer token for `audience`, rejecting nonces that were already used.
+    ///
+    /// The nonce is only used up once `accept` accepts the message sent with the token.
+    pub fn check_token(&mut self, token: &str, audience: &str) -> Result<AuthToken> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let leeway = self.max_clock_skew;
+        let auth = check_auth_token(token, audience, now, leeway)?;
+        self.token_nonces
+            .retain(|_, exp| exp.saturating_add(leeway) >= now);
+        ensure!(
+            !self.token_nonces.contains_key(&auth.nonce),
+            "Replayed token: jti={}",
+            auth.nonce
+        );
+        Ok(auth)
+    }
+
+    /// Verifies and stores a message sent with `auth`, a token checked by `check_token`.
+    ///
+    /// The nonces of the message and the token are only recorded once every check
+    /// passed, so a rejected request can neither use them up nor move them forward.
+    pub fn accept(&mut self, data: &[u8], auth: Option<&AuthToken>) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        if let Some(auth) = auth {
+            let (_, raw) = check_delegated_message(data)?;
+            ensure!(
+                auth.issuer.as_slice() == raw.from_address.as_slice(),
+                "Token of 0x{} can't send messages from 0x{}",
+                hex::encode(auth.issuer),
+                hex::encode(&raw.from_address)
+            );
+        }
+        let (msg, raw) = self.checker.check(data, now)?;
+        if let Some(auth) = auth {
+            self.token_nonces
+                .insert(auth.nonce.clone(), auth.expires_at);
+        }
+        writeln!(self.store, "{}", hex::encode(data))?;
+        self.store.flush()?;
+        info!(
+            "Accepted message: from=0x{} nonce={} timestamp={} payload_len={}",
+            hex::encode(&raw.from_address),
+            msg.nonce,
+            raw.timestamp,
+            raw.payload.len()
+        );
+        Ok(())
+    }
+}
+
+/// Handles a request posting a message to `path`.
+pub fn handle(
+    ingest: &mut Ingest,
+    path: &str,
+    auth_audience: Option<&str>,
+    request: &mut Request,
+) -> Result<()> {
+    ensure!(request.url() == path, "Not found: {}", request.url());
+    ensure!(
+        request.method() == &Method::Post,
+        "Method not allowed: {}",
+        request.method()
+    );
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("content-type"))
+        .map(|h| h.value.as_str().to_string())
+        .unwrap_or_default();
+    ensure!(
+        content_type == CONTENT_TYPE_DEPHY,
+        "Unsupported content type: {:?}",
+        content_type
+    );
+    let auth = match auth_audience {
+        Some(audience) => {
+            let header = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("authorization"))
+                .map(|h| h.value.as_str().to_string())
+                .unwrap_or_default();
+            let token = parse_bearer(&header).ok_or(anyhow!("Bearer token required!"))?;
+            Some(ingest.check_token(token, audience)?)
+        }
+        None => None,
+    };
+
+    let mut data = vec![];
+    request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_BODY_SIZE {
+        bail!("Message is larger than {} bytes", MAX_BODY_SIZE);
+    }
+    ingest.accept(&data, auth.as_ref())
+}
+
+/// Status code and JSON body of the response to a request handled with `ret`.
+pub fn response_body(ret: &Result<()>) -> (u16, Value) {
+    match ret {
+        Ok(()) => (200, json!({ "ok": true })),
+        Err(e) => (400, json!({ "ok": false, "error": e.to_string() })),
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use dephy_ingest::*;
+use log::*;
+use std::fs;
+use std::path::PathBuf;
+use tiny_http::{Header, Response, Server};
+
+/// Stand-in of the DePHY ingest service for testing devices offline.
+///
+/// Point `DEPHY_ENDPOINT_HTTP` in `build.env` to `http://<host>:<port>/dephy/signed_message`.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, default_value = "0.0.0.0:3883")]
+    listen: String,
+
+    /// Path to accept messages on
+    #[arg(long, default_value = "/dephy/signed_message")]
+    path: String,
+
+    /// File that accepted messages are appended to, one hex-encoded message per line
+    #[arg(long, default_value = "dephy_messages.hex")]
+    store: PathBuf,
+
+    /// Maximum difference in seconds between the message timestamp and local time
+    #[arg(long, default_value_t = 300)]
+    max_clock_skew: u64,
+
+    /// Require a device bearer token issued for this audience, like `http://<host>:<port>`,
+    /// signed by the sender of the message
+    #[arg(long)]
+    auth_audience: Option<String>,
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .init();
+    let cli = Cli::parse();
+
+    if let Some(dir) = cli.store.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    let mut ingest = Ingest::open(&cli.store, cli.max_clock_skew)?;
+    let server = Server::http(&cli.listen).map_err(|e| anyhow!("{}", e))?;
+    info!("Listening on http://{}{}", cli.listen, cli.path);
+
+    for mut request in server.incoming_requests() {
+        let ret = handle(
+            &mut ingest,
+            &cli.path,
+            cli.auth_audience.as_deref(),
+            &mut request,
+        );
+        if let Err(e) = &ret {
+            warn!("Rejected {} {}: {}", request.method(), request.url(), e);
+        }
+        let (status, body) = response_body(&ret);
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(Header::from_bytes("content-type", "application/json").unwrap());
+        if let Err(e) = request.respond(response) {
+            error!("Failed to respond: {}", e);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use dephy_ingest::*;
+use dephy_message::*;
+use prost::Message;
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Method, Request, TestRequest};
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const PEER_KEY_HEX: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+const AUDIENCE: &str = "http://127.0.0.1:3883";
+const PATH: &str = "/dephy/signed_message";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn peer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(PEER_KEY_HEX).unwrap()).unwrap()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Empty store file for the test `name`.
+fn store(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("dephy-ingest-{}-{}.hex", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn message(nonce: u64) -> Vec<u8> {
+    MessageBuilder::new()
+        .timestamp(now())
+        .nonce(nonce)
+        .payload("hello")
+        .build(&signer())
+        .unwrap()
+        .encode_to_vec()
+}
+
+fn token<S: Signer>(signer: &S, nonce: u8) -> String {
+    sign_auth_token(signer, AUDIENCE, now(), 60, &[nonce; 16]).unwrap()
+}
+
+#[test]
+fn rejects_replayed_messages() {
+    let path = store("replay");
+    let mut ingest = Ingest::open(&path, 300).unwrap();
+    ingest.accept(&message(1), None).unwrap();
+    assert!(ingest.accept(&message(1), None).is_err());
+    ingest.accept(&message(2), None).unwrap();
+
+    let stale = MessageBuilder::new()
+        .timestamp(now() - 3600)
+        .nonce(3)
+        .build(&signer())
+        .unwrap()
+        .encode_to_vec();
+    assert!(ingest.accept(&stale, None).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rejected_requests_do_not_use_up_nonces() {
+    let path = store("nonce-burn");
+    let mut ingest = Ingest::open(&path, 300).unwrap();
+
+    // A token of another issuer must neither accept the message nor use up its nonce.
+    let msg = message(5);
+    let other = ingest.check_token(&token(&peer(), 1), AUDIENCE).unwrap();
+    assert!(ingest.accept(&msg, Some(&other)).is_err());
+    let auth = ingest.check_token(&token(&signer(), 2), AUDIENCE).unwrap();
+    ingest.accept(&msg, Some(&auth)).unwrap();
+
+    // A request rejected for its body must not use up its token.
+    let token = token(&signer(), 3);
+    let request = TestRequest::new()
+        .with_method(Method::Post)
+        .with_path(PATH)
+        .with_header(Header::from_bytes("content-type", "application/x-dephy").unwrap())
+        .with_header(
+            Header::from_bytes("authorization", format!("Bearer {}", token).as_bytes()).unwrap(),
+        )
+        .with_body("not a message");
+    let mut request: Request = request.into();
+    assert!(handle(&mut ingest, PATH, Some(AUDIENCE), &mut request).is_err());
+    let auth = ingest.check_token(&token, AUDIENCE).unwrap();
+    ingest.accept(&message(6), Some(&auth)).unwrap();
+
+    // Until the message sent with it is accepted.
+    assert!(ingest.check_token(&token, AUDIENCE).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn reloads_nonces_from_store() {
+    let path = store("reload");
+    let mut ingest = Ingest::open(&path, 300).unwrap();
+    ingest.accept(&message(10), None).unwrap();
+    ingest.accept(&message(11), None).unwrap();
+    drop(ingest);
+
+    let mut ingest = Ingest::open(&path, 300).unwrap();
+    assert!(ingest.accept(&message(11), None).is_err());
+    ingest.accept(&message(12), None).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn response_json() {
+    assert_eq!(response_body(&Ok(())), (200, json!({ "ok": true })));
+    let (status, body) = response_body(&Err(anyhow::anyhow!("Bearer token required!")));
+    assert_eq!(status, 400);
+    assert_eq!(
+        body,
+        json!({ "ok": false, "error": "Bearer token required!" })
+    );
+}
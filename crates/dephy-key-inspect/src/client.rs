@@ -0,0 +1,105 @@
+//! Host side of the serial protocol, for provisioning stations talking to a device in
+//! key inspect mode.
+
+use crate::serial::{
+    verify_challenge_signature, FirmwareInfo, FrameDecoder, IdentityInfo, SerialRequest,
+    SerialResponse,
+};
+use alloc::string::ToString;
+use anyhow::{anyhow, bail, Result};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+/// Client over the console UART of a device, or anything else carrying its bytes.
+///
+/// Reads block until a response arrives, so `io` should have a read timeout, like the
+/// one of a serial port.
+pub struct SerialClient<T> {
+    io: T,
+    decoder: FrameDecoder,
+    pending: VecDeque<u8>,
+    seq: u8,
+}
+
+impl<T: Read + Write> SerialClient<T> {
+    pub fn new(io: T) -> Self {
+        Self {
+            io,
+            decoder: FrameDecoder::new(),
+            pending: VecDeque::new(),
+            seq: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+
+    /// Sends `req` and waits for its response, skipping log output and responses to
+    /// earlier requests.
+    pub fn request(&mut self, req: &SerialRequest) -> Result<SerialResponse> {
+        self.seq = self.seq.wrapping_add(1);
+        self.io.write_all(&req.encode(self.seq))?;
+        self.io.flush()?;
+
+        let mut buf = [0u8; 256];
+        loop {
+            while let Some(byte) = self.pending.pop_front() {
+                let Some(packet) = self.decoder.push(byte) else {
+                    continue;
+                };
+                match SerialResponse::decode(&packet) {
+                    Ok((seq, res)) if seq == self.seq => return Ok(res),
+                    _ => continue,
+                }
+            }
+            let len = self.io.read(&mut buf)?;
+            if len == 0 {
+                bail!("Serial port closed while waiting for a response!");
+            }
+            self.pending.extend(&buf[..len]);
+        }
+    }
+
+    pub fn get_identity(&mut self, identity: &str) -> Result<IdentityInfo> {
+        match self.request(&SerialRequest::GetIdentity {
+            identity: identity.to_string(),
+        })? {
+            SerialResponse::Identity(info) => Ok(info),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    pub fn sign_challenge(&mut self, identity: &str, challenge: &[u8]) -> Result<[u8; 65]> {
+        match self.request(&SerialRequest::SignChallenge {
+            identity: identity.to_string(),
+            challenge: challenge.to_vec(),
+        })? {
+            SerialResponse::Signature(signature) => Ok(signature),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    pub fn get_firmware_info(&mut self) -> Result<FirmwareInfo> {
+        match self.request(&SerialRequest::GetFirmwareInfo)? {
+            SerialResponse::FirmwareInfo(info) => Ok(info),
+            res => Err(unexpected(res)),
+        }
+    }
+
+    /// Gets `identity` and checks that the device holds its key by having `challenge`
+    /// signed, which should be fresh random bytes.
+    pub fn verify_identity(&mut self, identity: &str, challenge: &[u8]) -> Result<IdentityInfo> {
+        let info = self.get_identity(identity)?;
+        let signature = self.sign_challenge(identity, challenge)?;
+        verify_challenge_signature(challenge, &signature, &info.address)?;
+        Ok(info)
+    }
+}
+
+fn unexpected(res: SerialResponse) -> anyhow::Error {
+    match res {
+        SerialResponse::Error(message) => anyhow!("Device error: {}", message),
+        res => anyhow!("Unexpected response: {:?}", res),
+    }
+}
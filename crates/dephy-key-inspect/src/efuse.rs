@@ -0,0 +1,159 @@
+//! Storage of keys in eFuse, with an in-memory simulator for host tests.
+
+use anyhow::{anyhow, ensure, Result};
+
+/// Number of key blocks, `KEY0` to `KEY5` of the ESP32-C3.
+pub const EFUSE_KEY_BLOCKS: usize = 6;
+/// Key purpose of blocks holding user data, like device keys. Other purposes are for
+/// flash encryption, secure boot digests or HMAC keys, which the CPU must not read.
+pub const EFUSE_KEY_PURPOSE_USER: u8 = 0;
+
+/// Key blocks of the eFuse, numbered `0` for `KEY0` to `5` for `KEY5`.
+///
+/// Programming eFuse is irreversible: bits only go from 0 to 1, and a write protected
+/// block can't be programmed anymore.
+pub trait EfuseStorage {
+    /// Whether `block` is free for a new key, like `esp_efuse_key_block_unused`: all bits
+    /// are zero, and it is neither write nor read protected.
+    fn key_block_unused(&self, block: usize) -> bool;
+
+    /// Key purpose of `block`, `EFUSE_KEY_PURPOSE_USER` unless it was burnt for another
+    /// purpose.
+    fn key_purpose(&self, block: usize) -> Result<u8>;
+
+    /// Reads the 256 bits of `block`, read protected blocks read as zeros.
+    fn read_key(&self, block: usize) -> Result<[u8; 32]>;
+
+    /// Programs `key` into `block` and write protects it in one batch, nothing is written
+    /// when either fails.
+    fn write_key(&mut self, block: usize, key: &[u8; 32]) -> Result<()>;
+}
+
+/// Checks that `block` may hold a device key, which rules out blocks burnt for another
+/// purpose.
+pub fn check_key_block<E: EfuseStorage>(efuse: &E, block: usize) -> Result<()> {
+    let purpose = efuse.key_purpose(block)?;
+    ensure!(
+        purpose == EFUSE_KEY_PURPOSE_USER,
+        "eFuse key block {} is used for key purpose {}!",
+        block,
+        purpose
+    );
+    Ok(())
+}
+
+/// The key in `block`, `None` when the block is unused.
+pub fn read_key_block<E: EfuseStorage>(efuse: &E, block: usize) -> Result<Option<[u8; 32]>> {
+    check_key_block(efuse, block)?;
+    if efuse.key_block_unused(block) {
+        return Ok(None);
+    }
+    efuse.read_key(block).map(Some)
+}
+
+fn check_block(block: usize) -> Result<()> {
+    ensure!(block < EFUSE_KEY_BLOCKS, "Bad eFuse key block: {}", block);
+    Ok(())
+}
+
+/// In-memory eFuse key blocks with the write-once semantics of the real ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimulatedEfuse {
+    blocks: [[u8; 32]; EFUSE_KEY_BLOCKS],
+    write_protected: [bool; EFUSE_KEY_BLOCKS],
+    read_protected: [bool; EFUSE_KEY_BLOCKS],
+    purposes: [u8; EFUSE_KEY_BLOCKS],
+}
+
+impl SimulatedEfuse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Programs the 1 bits of `bits` into `block`, like `esp_efuse_write_field_blob`.
+    ///
+    /// Programming a bit twice is refused, as ESP-IDF does.
+    pub fn program(&mut self, block: usize, bits: &[u8; 32]) -> Result<()> {
+        self.check_program(block, bits)?;
+        for (b, new) in self.blocks[block].iter_mut().zip(bits) {
+            *b |= new;
+        }
+        Ok(())
+    }
+
+    fn check_program(&self, block: usize, bits: &[u8; 32]) -> Result<()> {
+        check_block(block)?;
+        ensure!(
+            !self.write_protected[block],
+            "eFuse key block {} is write protected!",
+            block
+        );
+        ensure!(
+            self.blocks[block]
+                .iter()
+                .zip(bits)
+                .all(|(b, new)| b & new == 0),
+            "Repeated programming of eFuse key block {}!",
+            block
+        );
+        Ok(())
+    }
+
+    pub fn set_write_protect(&mut self, block: usize) -> Result<()> {
+        check_block(block)?;
+        self.write_protected[block] = true;
+        Ok(())
+    }
+
+    pub fn set_read_protect(&mut self, block: usize) -> Result<()> {
+        check_block(block)?;
+        self.read_protected[block] = true;
+        Ok(())
+    }
+
+    /// Burns the key purpose of `block`, which can be done only once.
+    pub fn set_key_purpose(&mut self, block: usize, purpose: u8) -> Result<()> {
+        check_block(block)?;
+        ensure!(
+            self.purposes[block] == EFUSE_KEY_PURPOSE_USER,
+            "Key purpose of eFuse key block {} is already burnt!",
+            block
+        );
+        self.purposes[block] = purpose;
+        Ok(())
+    }
+
+    pub fn is_write_protected(&self, block: usize) -> bool {
+        self.write_protected.get(block).copied().unwrap_or(false)
+    }
+}
+
+impl EfuseStorage for SimulatedEfuse {
+    fn key_block_unused(&self, block: usize) -> bool {
+        block < EFUSE_KEY_BLOCKS
+            && !self.write_protected[block]
+            && !self.read_protected[block]
+            && self.purposes[block] == EFUSE_KEY_PURPOSE_USER
+            && self.blocks[block].iter().all(|b| *b == 0)
+    }
+
+    fn key_purpose(&self, block: usize) -> Result<u8> {
+        check_block(block)?;
+        Ok(self.purposes[block])
+    }
+
+    fn read_key(&self, block: usize) -> Result<[u8; 32]> {
+        check_block(block)?;
+        if self.read_protected[block] {
+            return Ok([0; 32]);
+        }
+        Ok(self.blocks[block])
+    }
+
+    fn write_key(&mut self, block: usize, key: &[u8; 32]) -> Result<()> {
+        self.check_program(block, key)
+            .map_err(|e| anyhow!("Batch write cancelled: {}", e))?;
+        self.program(block, key)?;
+        self.set_write_protect(block)
+    }
+}
@@ -0,0 +1,185 @@
+//! Continuous health tests of NIST SP 800-90B section 4.4 on bytes of the hardware RNG.
+//!
+//! The ESP32-C3 RNG output is already conditioned, so the tests can't assess its entropy,
+//! they catch a broken or stuck source before its output becomes a key.
+
+/// Negative log2 of the false positive probability of each test, 2^-20 as recommended.
+pub const HEALTH_ALPHA_LOG2: u32 = 20;
+/// Window size of the adaptive proportion test for non-binary samples.
+pub const APT_WINDOW_SIZE: u32 = 512;
+/// Min-entropy claimed for every byte of RNG output, in bits.
+pub const DEFAULT_MIN_ENTROPY_BITS: u8 = 4;
+
+/// Repetition count test: fails when a sample repeats `cutoff` times in a row.
+#[derive(Clone, Debug)]
+pub struct RepetitionCountTest {
+    cutoff: u32,
+    last: Option<u8>,
+    count: u32,
+}
+
+impl RepetitionCountTest {
+    /// Cutoff `1 + ceil(20 / H)` for `H` bits of min-entropy per sample.
+    pub fn new(min_entropy_bits: u8) -> Self {
+        assert!(
+            (1..=8).contains(&min_entropy_bits),
+            "Bad min-entropy per byte: {}",
+            min_entropy_bits
+        );
+        let cutoff = 1 + HEALTH_ALPHA_LOG2.div_ceil(min_entropy_bits as u32);
+        Self {
+            cutoff,
+            last: None,
+            count: 0,
+        }
+    }
+
+    pub fn cutoff(&self) -> u32 {
+        self.cutoff
+    }
+
+    /// Returns whether the test still passes after `sample`.
+    pub fn sample(&mut self, sample: u8) -> bool {
+        if self.last == Some(sample) {
+            self.count += 1;
+        } else {
+            self.last = Some(sample);
+            self.count = 1;
+        }
+        self.count < self.cutoff
+    }
+}
+
+/// Adaptive proportion test: fails when the first sample of a window of
+/// `APT_WINDOW_SIZE` samples shows up `cutoff` times in the window.
+#[derive(Clone, Debug)]
+pub struct AdaptiveProportionTest {
+    cutoff: u32,
+    first: u8,
+    count: u32,
+    seen: u32,
+}
+
+impl AdaptiveProportionTest {
+    /// Cutoff `1 + CRITBINOM(W, 2^-H, 1 - alpha)` for `H` bits of min-entropy per sample.
+    pub fn new(min_entropy_bits: u8) -> Self {
+        assert!(
+            (1..=8).contains(&min_entropy_bits),
+            "Bad min-entropy per byte: {}",
+            min_entropy_bits
+        );
+        Self {
+            cutoff: 1 + critbinom(APT_WINDOW_SIZE, min_entropy_bits),
+            first: 0,
+            count: 0,
+            seen: APT_WINDOW_SIZE,
+        }
+    }
+
+    pub fn cutoff(&self) -> u32 {
+        self.cutoff
+    }
+
+    /// Returns whether the test still passes after `sample`.
+    pub fn sample(&mut self, sample: u8) -> bool {
+        if self.seen == APT_WINDOW_SIZE {
+            self.first = sample;
+            self.count = 1;
+            self.seen = 1;
+            return true;
+        }
+        self.seen += 1;
+        if sample == self.first {
+            self.count += 1;
+        }
+        self.count < self.cutoff
+    }
+}
+
+/// Smallest `k` of which the binomial CDF of `n` trials with probability `2^-h` reaches
+/// `1 - 2^-HEALTH_ALPHA_LOG2`.
+fn critbinom(n: u32, h: u8) -> u32 {
+    let p = 1.0 / (1u32 << h) as f64;
+    let target = 1.0 - 1.0 / (1u64 << HEALTH_ALPHA_LOG2) as f64;
+    let mut pmf = (0..n).fold(1.0, |acc, _| acc * (1.0 - p));
+    let mut cdf = pmf;
+    let mut k = 0;
+    while cdf < target && k < n {
+        pmf *= (n - k) as f64 / (k + 1) as f64 * p / (1.0 - p);
+        cdf += pmf;
+        k += 1;
+    }
+    k
+}
+
+/// Both continuous health tests run over every byte drawn from the RNG.
+#[derive(Clone, Debug)]
+pub struct EntropyHealth {
+    rct: RepetitionCountTest,
+    apt: AdaptiveProportionTest,
+    /// Bytes tested in total.
+    pub samples: u64,
+    /// Bytes tested since the last failure.
+    pub healthy_samples: u64,
+    /// Bytes that failed the repetition count test.
+    pub rct_failures: u64,
+    /// Bytes that failed the adaptive proportion test.
+    pub apt_failures: u64,
+}
+
+impl Default for EntropyHealth {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_ENTROPY_BITS)
+    }
+}
+
+impl EntropyHealth {
+    pub fn new(min_entropy_bits: u8) -> Self {
+        Self {
+            rct: RepetitionCountTest::new(min_entropy_bits),
+            apt: AdaptiveProportionTest::new(min_entropy_bits),
+            samples: 0,
+            healthy_samples: 0,
+            rct_failures: 0,
+            apt_failures: 0,
+        }
+    }
+
+    pub fn rct_cutoff(&self) -> u32 {
+        self.rct.cutoff()
+    }
+
+    pub fn apt_cutoff(&self) -> u32 {
+        self.apt.cutoff()
+    }
+
+    /// Runs the tests over `data`, and returns whether every byte passed.
+    pub fn feed(&mut self, data: &[u8]) -> bool {
+        let mut passed = true;
+        for &b in data {
+            // Both tests see every sample, even after the other one fails.
+            let rct = self.rct.sample(b);
+            let apt = self.apt.sample(b);
+            self.samples += 1;
+            if !rct {
+                self.rct_failures += 1;
+            }
+            if !apt {
+                self.apt_failures += 1;
+            }
+            if rct && apt {
+                self.healthy_samples += 1;
+            } else {
+                self.healthy_samples = 0;
+                passed = false;
+            }
+        }
+        passed
+    }
+
+    /// Whether at least a full window of the adaptive proportion test passed since the
+    /// last failure, so that RNG output may be used for a key.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy_samples >= APT_WINDOW_SIZE as u64
+    }
+}
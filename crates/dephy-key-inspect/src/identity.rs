@@ -0,0 +1,109 @@
+//! Identities of the device, each one a key in its own eFuse key block.
+
+use crate::efuse::EFUSE_KEY_BLOCKS;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use anyhow::{anyhow, ensure, Result};
+
+/// Name of the identity of the device, which signs messages by default.
+pub const DEVICE_IDENTITY: &str = "device";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EfuseIdentity {
+    pub name: String,
+    /// eFuse key block of the key, `0` for `KEY0` to `5` for `KEY5`.
+    pub block: usize,
+}
+
+fn parse_block(s: &str) -> Result<usize> {
+    let block = s
+        .trim()
+        .trim_start_matches("KEY")
+        .parse::<usize>()
+        .map_err(|_| anyhow!("Bad eFuse key block: {:?}", s))?;
+    ensure!(block < EFUSE_KEY_BLOCKS, "Bad eFuse key block: {:?}", s);
+    Ok(block)
+}
+
+/// Parses the identities of the device: the device identity in `device_block`, then the
+/// `<name>:<block>` entries of `extra` separated by `;`, like `test:5`.
+///
+/// Key blocks in `reserved`, separated by `,`, are kept for flash encryption or secure
+/// boot digests and refused.
+pub fn parse_efuse_identities(
+    device_block: usize,
+    extra: &str,
+    reserved: &str,
+) -> Result<Vec<EfuseIdentity>> {
+    let reserved = reserved
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_block)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut ret = Vec::new();
+    ret.push(EfuseIdentity {
+        name: DEVICE_IDENTITY.to_string(),
+        block: device_block,
+    });
+    for entry in extra.split(';').filter(|s| !s.trim().is_empty()) {
+        let (name, block) = entry.split_once(':').ok_or(anyhow!(
+            "Bad eFuse identity, expected <name>:<block>: {:?}",
+            entry
+        ))?;
+        let name = name.trim();
+        ensure!(
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "Bad eFuse identity name: {:?}",
+            name
+        );
+        ret.push(EfuseIdentity {
+            name: name.to_string(),
+            block: parse_block(block)?,
+        });
+    }
+
+    for (i, identity) in ret.iter().enumerate() {
+        ensure!(
+            identity.block < EFUSE_KEY_BLOCKS,
+            "Bad eFuse key block of identity {}: {}",
+            identity.name,
+            identity.block
+        );
+        ensure!(
+            !reserved.contains(&identity.block),
+            "eFuse key block {} of identity {} is reserved!",
+            identity.block,
+            identity.name
+        );
+        for other in ret[..i].iter() {
+            ensure!(
+                other.name != identity.name,
+                "Duplicated eFuse identity: {}",
+                identity.name
+            );
+            ensure!(
+                other.block != identity.block,
+                "eFuse key block {} is shared by identities {} and {}!",
+                identity.block,
+                other.name,
+                identity.name
+            );
+        }
+    }
+    Ok(ret)
+}
+
+/// The identity named `name`.
+pub fn find_efuse_identity<'a>(
+    identities: &'a [EfuseIdentity],
+    name: &str,
+) -> Result<&'a EfuseIdentity> {
+    identities
+        .iter()
+        .find(|i| i.name == name)
+        .ok_or(anyhow!("Unknown eFuse identity: {}", name))
+}
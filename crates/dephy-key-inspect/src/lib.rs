@@ -0,0 +1,28 @@
+//! Key inspect mode logic shared by the firmware and host tests: the checks that run
+//! before the device key is burnt into eFuse, the state machine around them, and the
+//! serial protocol provisioning stations talk to the device with.
+//!
+//! The crate is `no_std` with `alloc`, the `std` feature is enabled by default.
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+pub mod client;
+pub mod efuse;
+pub mod health;
+pub mod identity;
+pub mod serial;
+pub mod status;
+pub mod wait;
+
+#[cfg(feature = "std")]
+pub use client::*;
+pub use efuse::*;
+pub use health::*;
+pub use identity::*;
+pub use serial::*;
+pub use status::*;
+pub use wait::*;
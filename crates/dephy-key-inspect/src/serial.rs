@@ -0,0 +1,412 @@
+//! Request/response protocol of the key inspect mode over the console UART, for
+//! provisioning stations.
+//!
+//! Packets are COBS-encoded and sent between `0x00` delimiters, so that log output on the
+//! same UART ends up in frames which fail to decode and get skipped. A packet is
+//! `kind | seq | body | crc32`, where `body` is a CBOR map with integer keys, `seq` is
+//! echoed in the response, and the CRC-32 (ISO-HDLC) is little-endian:
+//!
+//! | Kind   | Packet               | Body                                                      |
+//! |--------|----------------------|-----------------------------------------------------------|
+//! | `0x01` | get-identity         | 1: identity name                                          |
+//! | `0x02` | sign-challenge       | 1: identity name, 2: challenge                            |
+//! | `0x03` | get-firmware-info    | empty                                                     |
+//! | `0x81` | identity             | 1: name, 2: key block, 3: SEC1 public key, 4: address     |
+//! | `0x82` | signature            | 1: EIP-191 signature of `challenge_message(challenge)`    |
+//! | `0x83` | firmware info        | 1: name, 2: version, 3: device name, 4: status, 5: progress |
+//! | `0xff` | error                | 1: message                                                |
+
+use crate::efuse::EFUSE_KEY_BLOCKS;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use anyhow::{anyhow, bail, ensure, Result};
+use dephy_message::cbor::CborValue;
+use dephy_message::crypto::get_eth_address_bytes;
+use dephy_message::eth::{eip191_digest, recover_eth_signer};
+
+/// Largest packet accepted, frames beyond it are dropped.
+pub const SERIAL_MAX_PACKET: usize = 512;
+/// Challenges to sign must be this long at least, so that they can't be guessed.
+pub const SERIAL_MIN_CHALLENGE: usize = 16;
+pub const SERIAL_MAX_CHALLENGE: usize = 64;
+
+const KIND_GET_IDENTITY: u8 = 0x01;
+const KIND_SIGN_CHALLENGE: u8 = 0x02;
+const KIND_GET_FIRMWARE_INFO: u8 = 0x03;
+const KIND_IDENTITY: u8 = 0x81;
+const KIND_SIGNATURE: u8 = 0x82;
+const KIND_FIRMWARE_INFO: u8 = 0x83;
+const KIND_ERROR: u8 = 0xff;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SerialRequest {
+    GetIdentity {
+        identity: String,
+    },
+    SignChallenge {
+        identity: String,
+        challenge: Vec<u8>,
+    },
+    GetFirmwareInfo,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentityInfo {
+    pub name: String,
+    pub block: usize,
+    /// SEC1-encoded public key.
+    pub public_key: Vec<u8>,
+    pub address: [u8; 20],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    pub name: String,
+    pub version: String,
+    pub device_name: String,
+    /// Status of the key inspect mode, like `waiting-for-entropy` or `key-taken`.
+    pub status: String,
+    /// Progress of the entropy wait in percent.
+    pub progress: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SerialResponse {
+    Identity(IdentityInfo),
+    Signature([u8; 65]),
+    FirmwareInfo(FirmwareInfo),
+    Error(String),
+}
+
+/// What the device answers requests with.
+pub trait SerialDevice {
+    fn identity(&self, name: &str) -> Result<IdentityInfo>;
+    /// EIP-191 signature of `challenge_message(challenge)` with the key of `name`.
+    fn sign_challenge(&self, name: &str, challenge: &[u8]) -> Result<[u8; 65]>;
+    fn firmware_info(&self) -> FirmwareInfo;
+}
+
+/// Message signed for a challenge. The fixed prefix keeps it from being a valid message
+/// of another protocol, like a Sign-In with Ethereum message.
+pub fn challenge_message(challenge: &[u8]) -> String {
+    format!("DePHY key inspect challenge: 0x{}", hex::encode(challenge))
+}
+
+/// Checks that `signature` of `challenge` was made by the key of `address`.
+pub fn verify_challenge_signature(
+    challenge: &[u8],
+    signature: &[u8; 65],
+    address: &[u8; 20],
+) -> Result<()> {
+    let digest = eip191_digest(challenge_message(challenge).as_bytes());
+    let signer = recover_eth_signer(digest, signature)?;
+    ensure!(
+        get_eth_address_bytes(&signer) == *address,
+        "Challenge not signed by 0x{}",
+        hex::encode(address)
+    );
+    Ok(())
+}
+
+fn check_challenge(challenge: &[u8]) -> Result<()> {
+    ensure!(
+        (SERIAL_MIN_CHALLENGE..=SERIAL_MAX_CHALLENGE).contains(&challenge.len()),
+        "Bad challenge length: {}",
+        challenge.len()
+    );
+    Ok(())
+}
+
+/// COBS encoding of `data`, without the delimiter.
+pub fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut ret = vec![0];
+    let mut code_at = 0;
+    let mut code = 1u8;
+    for (i, &b) in data.iter().enumerate() {
+        if b == 0 {
+            ret[code_at] = code;
+            code_at = ret.len();
+            ret.push(0);
+            code = 1;
+            continue;
+        }
+        ret.push(b);
+        code += 1;
+        // A full block at the end is not followed by an empty one.
+        if code == 0xff && i + 1 < data.len() {
+            ret[code_at] = code;
+            code_at = ret.len();
+            ret.push(0);
+            code = 1;
+        }
+    }
+    ret[code_at] = code;
+    ret
+}
+
+pub fn cobs_decode(data: &[u8]) -> Result<Vec<u8>> {
+    let mut ret = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let code = data[i] as usize;
+        ensure!(code != 0, "Bad COBS frame: zero byte at {}", i);
+        let end = i + code;
+        ensure!(end <= data.len(), "Truncated COBS frame!");
+        let block = &data[i + 1..end];
+        ensure!(!block.contains(&0), "Bad COBS frame: zero byte in block");
+        ret.extend_from_slice(block);
+        i = end;
+        if code != 0xff && i < data.len() {
+            ret.push(0);
+        }
+    }
+    Ok(ret)
+}
+
+/// CRC-32/ISO-HDLC, as in zlib.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Wire bytes of a packet: the COBS frame between delimiters.
+fn encode_packet(kind: u8, seq: u8, body: Vec<(i64, CborValue)>) -> Vec<u8> {
+    let mut packet = vec![kind, seq];
+    CborValue::Map(
+        body.into_iter()
+            .map(|(k, v)| (CborValue::int(k), v))
+            .collect(),
+    )
+    .encode_to(&mut packet);
+    let crc = crc32(&packet);
+    packet.extend_from_slice(&crc.to_le_bytes());
+
+    let mut ret = vec![0];
+    ret.extend(cobs_encode(&packet));
+    ret.push(0);
+    ret
+}
+
+/// Kind, seq and body of a COBS-decoded packet.
+fn decode_packet(packet: &[u8]) -> Result<(u8, u8, CborValue)> {
+    ensure!(
+        packet.len() >= 7,
+        "Packet too short: {} bytes",
+        packet.len()
+    );
+    let (packet, crc) = packet.split_at(packet.len() - 4);
+    ensure!(
+        crc32(packet).to_le_bytes() == crc,
+        "Bad packet CRC, maybe mixed with log output"
+    );
+    let body = CborValue::decode(&packet[2..])?;
+    ensure!(
+        matches!(body, CborValue::Map(_)),
+        "Bad packet body, expected a map!"
+    );
+    Ok((packet[0], packet[1], body))
+}
+
+fn text(body: &CborValue, key: i64) -> Result<String> {
+    match body.get(key) {
+        Some(CborValue::Text(s)) => Ok(s.clone()),
+        _ => bail!("Bad or missing text {} in packet", key),
+    }
+}
+
+fn bytes(body: &CborValue, key: i64) -> Result<Vec<u8>> {
+    Ok(body
+        .get(key)
+        .and_then(CborValue::as_bytes)
+        .ok_or(anyhow!("Bad or missing bytes {} in packet", key))?
+        .to_vec())
+}
+
+fn uint(body: &CborValue, key: i64) -> Result<u64> {
+    body.get(key)
+        .and_then(CborValue::as_u64)
+        .ok_or(anyhow!("Bad or missing integer {} in packet", key))
+}
+
+impl SerialRequest {
+    pub fn encode(&self, seq: u8) -> Vec<u8> {
+        match self {
+            SerialRequest::GetIdentity { identity } => encode_packet(
+                KIND_GET_IDENTITY,
+                seq,
+                vec![(1, CborValue::Text(identity.clone()))],
+            ),
+            SerialRequest::SignChallenge {
+                identity,
+                challenge,
+            } => encode_packet(
+                KIND_SIGN_CHALLENGE,
+                seq,
+                vec![
+                    (1, CborValue::Text(identity.clone())),
+                    (2, CborValue::Bytes(challenge.clone())),
+                ],
+            ),
+            SerialRequest::GetFirmwareInfo => encode_packet(KIND_GET_FIRMWARE_INFO, seq, vec![]),
+        }
+    }
+
+    /// Seq and request of a COBS-decoded packet.
+    pub fn decode(packet: &[u8]) -> Result<(u8, Self)> {
+        let (kind, seq, body) = decode_packet(packet)?;
+        let req = match kind {
+            KIND_GET_IDENTITY => SerialRequest::GetIdentity {
+                identity: text(&body, 1)?,
+            },
+            KIND_SIGN_CHALLENGE => SerialRequest::SignChallenge {
+                identity: text(&body, 1)?,
+                challenge: bytes(&body, 2)?,
+            },
+            KIND_GET_FIRMWARE_INFO => SerialRequest::GetFirmwareInfo,
+            _ => bail!("Unknown request kind: 0x{:02x}", kind),
+        };
+        Ok((seq, req))
+    }
+}
+
+impl SerialResponse {
+    pub fn encode(&self, seq: u8) -> Vec<u8> {
+        match self {
+            SerialResponse::Identity(info) => encode_packet(
+                KIND_IDENTITY,
+                seq,
+                vec![
+                    (1, CborValue::Text(info.name.clone())),
+                    (2, CborValue::Unsigned(info.block as u64)),
+                    (3, CborValue::Bytes(info.public_key.clone())),
+                    (4, CborValue::Bytes(info.address.to_vec())),
+                ],
+            ),
+            SerialResponse::Signature(signature) => encode_packet(
+                KIND_SIGNATURE,
+                seq,
+                vec![(1, CborValue::Bytes(signature.to_vec()))],
+            ),
+            SerialResponse::FirmwareInfo(info) => encode_packet(
+                KIND_FIRMWARE_INFO,
+                seq,
+                vec![
+                    (1, CborValue::Text(info.name.clone())),
+                    (2, CborValue::Text(info.version.clone())),
+                    (3, CborValue::Text(info.device_name.clone())),
+                    (4, CborValue::Text(info.status.clone())),
+                    (5, CborValue::Unsigned(info.progress as u64)),
+                ],
+            ),
+            SerialResponse::Error(message) => {
+                encode_packet(KIND_ERROR, seq, vec![(1, CborValue::Text(message.clone()))])
+            }
+        }
+    }
+
+    /// Seq and response of a COBS-decoded packet.
+    pub fn decode(packet: &[u8]) -> Result<(u8, Self)> {
+        let (kind, seq, body) = decode_packet(packet)?;
+        let res = match kind {
+            KIND_IDENTITY => {
+                let block = uint(&body, 2)? as usize;
+                ensure!(block < EFUSE_KEY_BLOCKS, "Bad eFuse key block: {}", block);
+                SerialResponse::Identity(IdentityInfo {
+                    name: text(&body, 1)?,
+                    block,
+                    public_key: bytes(&body, 3)?,
+                    address: bytes(&body, 4)?
+                        .try_into()
+                        .map_err(|_| anyhow!("Bad address length!"))?,
+                })
+            }
+            KIND_SIGNATURE => SerialResponse::Signature(
+                bytes(&body, 1)?
+                    .try_into()
+                    .map_err(|_| anyhow!("Bad signature length!"))?,
+            ),
+            KIND_FIRMWARE_INFO => SerialResponse::FirmwareInfo(FirmwareInfo {
+                name: text(&body, 1)?,
+                version: text(&body, 2)?,
+                device_name: text(&body, 3)?,
+                status: text(&body, 4)?,
+                progress: uint(&body, 5)?.min(100) as u8,
+            }),
+            KIND_ERROR => SerialResponse::Error(text(&body, 1)?),
+            _ => bail!("Unknown response kind: 0x{:02x}", kind),
+        };
+        Ok((seq, res))
+    }
+}
+
+/// Splits received bytes into COBS-decoded packets.
+#[derive(Clone, Debug, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    overflow: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a received byte, and returns the packet of the frame it ends if any. Frames
+    /// that fail to decode, like log output, are dropped.
+    pub fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if byte != 0 {
+            // Encoded packets are at most one byte in 254 longer.
+            if self.buf.len() > SERIAL_MAX_PACKET + SERIAL_MAX_PACKET / 254 + 1 {
+                self.overflow = true;
+                self.buf.clear();
+            } else {
+                self.buf.push(byte);
+            }
+            return None;
+        }
+        let frame = core::mem::take(&mut self.buf);
+        if core::mem::take(&mut self.overflow) || frame.is_empty() {
+            return None;
+        }
+        cobs_decode(&frame).ok()
+    }
+}
+
+/// Answers the request in `packet` with `device`, and returns the wire bytes of the
+/// response. Packets which aren't valid requests are ignored, unless only their kind is
+/// unknown.
+pub fn handle_serial_packet<D: SerialDevice>(device: &D, packet: &[u8]) -> Option<Vec<u8>> {
+    let (seq, req) = match SerialRequest::decode(packet) {
+        Ok(req) => req,
+        Err(e) => {
+            let (_, seq, _) = decode_packet(packet).ok()?;
+            return Some(SerialResponse::Error(e.to_string()).encode(seq));
+        }
+    };
+    let res = match req {
+        SerialRequest::GetIdentity { identity } => {
+            device.identity(&identity).map(SerialResponse::Identity)
+        }
+        SerialRequest::SignChallenge {
+            identity,
+            challenge,
+        } => check_challenge(&challenge)
+            .and_then(|_| device.sign_challenge(&identity, &challenge))
+            .map(SerialResponse::Signature),
+        SerialRequest::GetFirmwareInfo => Ok(SerialResponse::FirmwareInfo(device.firmware_info())),
+    };
+    let res = res.unwrap_or_else(|e| SerialResponse::Error(e.to_string()));
+    Some(res.encode(seq))
+}
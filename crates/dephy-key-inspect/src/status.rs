@@ -0,0 +1,212 @@
+//! State machine of the key inspect mode, advanced once a second by the firmware.
+
+use crate::efuse::{check_key_block, read_key_block, EfuseStorage};
+use crate::health::EntropyHealth;
+use crate::identity::EfuseIdentity;
+use crate::wait::EntropyWaitPolicy;
+use alloc::string::String;
+use alloc::vec::Vec;
+use anyhow::{anyhow, ensure, Result};
+use dephy_message::crypto::get_eth_address;
+use k256::{PublicKey, SecretKey};
+use log::{info, warn};
+
+/// RNG output run through the health tests every second while waiting.
+pub const ENTROPY_SAMPLE_BYTES: usize = 1024;
+
+/// Source of random bytes, the hardware RNG on the device.
+pub trait EntropySource {
+    fn fill_bytes(&mut self, buf: &mut [u8]);
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyInspectStatus {
+    Init,
+    WaitingForEntropy {
+        secs_waited: u64,
+    },
+    ShouldGenerateKey {
+        secs_waited: u64,
+    },
+    KeyTaken {
+        pubkey_hex: String,
+        addr_hex: String,
+        secs_waited: u64,
+    },
+}
+
+impl KeyInspectStatus {
+    /// Short name of the status, as reported over the serial protocol.
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyInspectStatus::Init => "init",
+            KeyInspectStatus::WaitingForEntropy { .. } => "waiting-for-entropy",
+            KeyInspectStatus::ShouldGenerateKey { .. } => "should-generate-key",
+            KeyInspectStatus::KeyTaken { .. } => "key-taken",
+        }
+    }
+}
+
+/// Everything the key inspect mode works with, so that it runs against simulated eFuse
+/// and RNG on the host.
+pub struct KeyInspect<E, R> {
+    pub efuse: E,
+    pub rng: R,
+    pub health: EntropyHealth,
+    pub policy: EntropyWaitPolicy,
+    /// Identities to hold keys for, the first one is the device identity.
+    pub identities: Vec<EfuseIdentity>,
+    /// Whether to generate the keys of missing extra identities as well, which takes an
+    /// explicit provisioning request, otherwise only a missing device key is generated.
+    pub provision_extra_identities: bool,
+}
+
+impl<E: EfuseStorage, R: EntropySource> KeyInspect<E, R> {
+    pub fn new(
+        efuse: E,
+        rng: R,
+        health: EntropyHealth,
+        policy: EntropyWaitPolicy,
+        identities: Vec<EfuseIdentity>,
+    ) -> Self {
+        assert!(!identities.is_empty(), "No eFuse identity!");
+        Self {
+            efuse,
+            rng,
+            health,
+            policy,
+            identities,
+            provision_extra_identities: false,
+        }
+    }
+
+    /// The device key, `None` when its key block is unused.
+    pub fn get_key(&self) -> Result<Option<[u8; 32]>> {
+        read_key_block(&self.efuse, self.identities[0].block)
+    }
+
+    /// Identities of which the key block is unused, after checking that every key block
+    /// may hold a key.
+    pub fn missing_identities(&self) -> Result<Vec<EfuseIdentity>> {
+        let mut ret = Vec::new();
+        for identity in self.identities.iter() {
+            check_key_block(&self.efuse, identity.block)?;
+            if self.efuse.key_block_unused(identity.block) {
+                ret.push(identity.clone());
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Missing identities of which the key is to be generated.
+    pub fn identities_to_generate(&self) -> Result<Vec<EfuseIdentity>> {
+        let mut ret = self.missing_identities()?;
+        if !self.provision_extra_identities {
+            ret.retain(|identity| *identity == self.identities[0]);
+        }
+        Ok(ret)
+    }
+
+    /// Address of every identity holding a key.
+    pub fn identity_addresses(&self) -> Result<Vec<(&EfuseIdentity, String)>> {
+        let mut ret = Vec::new();
+        for identity in self.identities.iter() {
+            if let Some(key) = read_key_block(&self.efuse, identity.block)? {
+                ret.push((identity, key_address(&key)?));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Advances `status` by one tick of a second.
+    pub fn next(&mut self, status: KeyInspectStatus) -> Result<KeyInspectStatus> {
+        Ok(match status {
+            KeyInspectStatus::Init => match self.get_key()? {
+                Some(key) if self.identities_to_generate()?.is_empty() => key_taken(&key)?,
+                _ => KeyInspectStatus::WaitingForEntropy { secs_waited: 1 },
+            },
+            KeyInspectStatus::WaitingForEntropy { secs_waited } => {
+                let mut buf = [0u8; ENTROPY_SAMPLE_BYTES];
+                self.rng.fill_bytes(&mut buf);
+                if !self.health.feed(&buf) {
+                    warn!(
+                        "RNG health tests failed: rct_failures={} apt_failures={}",
+                        self.health.rct_failures, self.health.apt_failures
+                    );
+                }
+                if self.policy.is_done(secs_waited, &self.health) {
+                    KeyInspectStatus::ShouldGenerateKey { secs_waited }
+                } else {
+                    KeyInspectStatus::WaitingForEntropy {
+                        secs_waited: secs_waited + 1,
+                    }
+                }
+            }
+            KeyInspectStatus::ShouldGenerateKey { secs_waited } => {
+                info!("Should generate key now!");
+                for identity in self.identities_to_generate()? {
+                    let Some(key) = self.generate_key() else {
+                        warn!("RNG output rejected for the key, waiting for healthy output...");
+                        return Ok(KeyInspectStatus::WaitingForEntropy { secs_waited });
+                    };
+                    self.efuse.write_key(identity.block, &key)?;
+                    info!(
+                        "Random key of identity {} has been written to eFuse key block {}!",
+                        identity.name, identity.block
+                    );
+                    let written = read_key_block(&self.efuse, identity.block)?;
+                    ensure!(
+                        written == Some(key),
+                        "Key read back from eFuse key block {} differs!",
+                        identity.block
+                    );
+                }
+                let key = self
+                    .get_key()?
+                    .ok_or(anyhow!("Device key missing after it was written!"))?;
+                key_taken(&key)?
+            }
+            KeyInspectStatus::KeyTaken {
+                pubkey_hex,
+                addr_hex,
+                secs_waited,
+            } => KeyInspectStatus::KeyTaken {
+                pubkey_hex,
+                addr_hex,
+                secs_waited: secs_waited + 1,
+            },
+        })
+    }
+
+    /// Draws the key from the RNG, unless the health tests failed on it or since the
+    /// last full window of output, or it is not a valid secp256k1 secret key.
+    fn generate_key(&mut self) -> Option<[u8; 32]> {
+        let mut buf = [0u8; 32];
+        self.rng.fill_bytes(&mut buf);
+        let healthy = self.health.feed(&buf) && self.health.is_healthy();
+        if healthy && SecretKey::from_slice(&buf).is_ok() {
+            Some(buf)
+        } else {
+            None
+        }
+    }
+}
+
+fn public_key(key: &[u8; 32]) -> Result<PublicKey> {
+    Ok(SecretKey::from_slice(key)
+        .map_err(|e| anyhow!("Bad key in eFuse: {}", e))?
+        .public_key())
+}
+
+fn key_address(key: &[u8; 32]) -> Result<String> {
+    Ok(get_eth_address(&public_key(key)?.into()))
+}
+
+fn key_taken(key: &[u8; 32]) -> Result<KeyInspectStatus> {
+    let key = public_key(key)?;
+    Ok(KeyInspectStatus::KeyTaken {
+        pubkey_hex: hex::encode(key.to_sec1_bytes()),
+        addr_hex: get_eth_address(&key.into()),
+        secs_waited: 0,
+    })
+}
@@ -0,0 +1,48 @@
+use crate::health::EntropyHealth;
+
+/// When the key inspect mode stops waiting for entropy and generates the key.
+///
+/// The wait ends after `max_wait_secs`, or earlier once `min_wait_secs` have passed and
+/// `min_healthy_samples` bytes of RNG output passed the health tests in a row. Either
+/// way, the key is only generated while the health tests pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntropyWaitPolicy {
+    pub max_wait_secs: u64,
+    pub min_wait_secs: u64,
+    /// Healthy samples to finish before `max_wait_secs`, `0` to always wait that long.
+    pub min_healthy_samples: u64,
+}
+
+impl EntropyWaitPolicy {
+    /// Whether the key may be generated after `secs_waited`.
+    pub fn is_done(&self, secs_waited: u64, health: &EntropyHealth) -> bool {
+        if !health.is_healthy() {
+            return false;
+        }
+        secs_waited >= self.max_wait_secs
+            || (self.min_healthy_samples > 0
+                && secs_waited >= self.min_wait_secs
+                && health.healthy_samples >= self.min_healthy_samples)
+    }
+
+    /// Progress of the wait in percent, `100` only when `is_done`.
+    pub fn progress(&self, secs_waited: u64, health: &EntropyHealth) -> u8 {
+        let mut ret = percent(secs_waited, self.max_wait_secs);
+        if self.min_healthy_samples > 0 {
+            let early = percent(secs_waited, self.min_wait_secs)
+                .min(percent(health.healthy_samples, self.min_healthy_samples));
+            ret = ret.max(early);
+        }
+        if self.is_done(secs_waited, health) {
+            100
+        } else {
+            ret.min(99)
+        }
+    }
+}
+
+fn percent(value: u64, total: u64) -> u8 {
+    (value.min(total) * 100)
+        .checked_div(total)
+        .map_or(100, |p| p as u8)
+}
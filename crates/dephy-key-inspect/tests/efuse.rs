@@ -0,0 +1,60 @@
+use dephy_key_inspect::*;
+
+const KEY: [u8; 32] = [0x5a; 32];
+
+#[test]
+fn bits_are_programmed_once() {
+    let mut efuse = SimulatedEfuse::new();
+    assert!(efuse.key_block_unused(4));
+
+    efuse.program(4, &[0x0f; 32]).unwrap();
+    assert!(!efuse.key_block_unused(4));
+    efuse.program(4, &[0xf0; 32]).unwrap();
+    assert_eq!(efuse.read_key(4).unwrap(), [0xff; 32]);
+
+    // Bits can't be cleared nor programmed again.
+    let mut efuse = SimulatedEfuse::new();
+    efuse.program(4, &[0x0f; 32]).unwrap();
+    assert!(efuse.program(4, &[0x01; 32]).is_err());
+    assert_eq!(efuse.read_key(4).unwrap(), [0x0f; 32]);
+}
+
+#[test]
+fn write_key_protects_the_block() {
+    let mut efuse = SimulatedEfuse::new();
+    efuse.write_key(4, &KEY).unwrap();
+    assert!(efuse.is_write_protected(4));
+    assert!(!efuse.key_block_unused(4));
+    assert_eq!(read_key_block(&efuse, 4).unwrap(), Some(KEY));
+    assert_eq!(read_key_block(&efuse, 5).unwrap(), None);
+
+    // Even bits still at zero can't be programmed anymore.
+    assert!(efuse.program(4, &[0x01; 32]).is_err());
+    assert!(efuse.write_key(4, &KEY).is_err());
+    assert_eq!(efuse.read_key(4).unwrap(), KEY);
+}
+
+#[test]
+fn failed_batch_writes_nothing() {
+    let mut efuse = SimulatedEfuse::new();
+    efuse.program(5, &[0x01; 32]).unwrap();
+    let before = efuse.clone();
+    assert!(efuse.write_key(5, &[0x03; 32]).is_err());
+    assert_eq!(efuse, before);
+    assert!(!efuse.is_write_protected(5));
+
+    assert!(efuse.write_key(EFUSE_KEY_BLOCKS, &KEY).is_err());
+}
+
+#[test]
+fn protected_blocks_are_used() {
+    let mut efuse = SimulatedEfuse::new();
+    efuse.set_write_protect(2).unwrap();
+    assert!(!efuse.key_block_unused(2));
+    assert_eq!(read_key_block(&efuse, 2).unwrap(), Some([0; 32]));
+
+    efuse.program(3, &KEY).unwrap();
+    efuse.set_read_protect(3).unwrap();
+    assert!(!efuse.key_block_unused(3));
+    assert_eq!(efuse.read_key(3).unwrap(), [0; 32]);
+}
@@ -0,0 +1,70 @@
+use dephy_key_inspect::*;
+
+/// xorshift64*, a stand-in for healthy RNG output.
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut state = 0x9e3779b97f4a7c15u64;
+    (0..len)
+        .map(|_| {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            (state.wrapping_mul(0x2545f4914f6cdd1d) >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn cutoffs_match_sp800_90b() {
+    // Table 2 of SP 800-90B and `1 + ceil(20 / H)`.
+    for (h, rct, apt) in [(1, 21, 311), (2, 11, 177), (4, 6, 62), (8, 4, 13)] {
+        let health = EntropyHealth::new(h);
+        assert_eq!(health.rct_cutoff(), rct, "H={}", h);
+        assert_eq!(health.apt_cutoff(), apt, "H={}", h);
+    }
+}
+
+#[test]
+fn random_output_passes() {
+    let mut health = EntropyHealth::default();
+    assert!(!health.is_healthy());
+    assert!(health.feed(&random_bytes(64 * 1024)));
+    assert!(health.is_healthy());
+    assert_eq!(health.samples, 64 * 1024);
+    assert_eq!(health.healthy_samples, 64 * 1024);
+    assert_eq!(health.rct_failures + health.apt_failures, 0);
+}
+
+#[test]
+fn stuck_output_fails_repetition_count() {
+    let mut health = EntropyHealth::default();
+    assert!(health.feed(&random_bytes(1024)));
+    assert!(health.is_healthy());
+
+    let cutoff = health.rct_cutoff() as usize;
+    assert!(health.feed(&vec![0x5a; cutoff - 1]));
+    assert!(!health.feed(&[0x5a]));
+    assert!(!health.is_healthy());
+    assert_eq!(health.rct_failures, 1);
+    assert_eq!(health.healthy_samples, 0);
+
+    // Still failing while stuck, healthy again after a full window of good output.
+    assert!(!health.feed(&[0x5a; 16]));
+    assert_eq!(health.rct_failures, 17);
+    assert!(health.feed(&random_bytes(APT_WINDOW_SIZE as usize)));
+    assert!(health.is_healthy());
+}
+
+#[test]
+fn biased_output_fails_adaptive_proportion() {
+    let mut health = EntropyHealth::default();
+    // Every other byte is the same, which never repeats in a row.
+    let biased: Vec<u8> = random_bytes(APT_WINDOW_SIZE as usize)
+        .into_iter()
+        .enumerate()
+        .map(|(i, b)| if i % 2 == 0 { 0 } else { b | 1 })
+        .collect();
+    assert!(!health.feed(&biased));
+    assert_eq!(health.rct_failures, 0);
+    assert!(health.apt_failures > 0);
+    assert!(!health.is_healthy());
+}
@@ -0,0 +1,55 @@
+use dephy_key_inspect::*;
+
+#[test]
+fn parses_identities() {
+    let identities = parse_efuse_identities(4, "test:5; attestation:KEY3;", "0,1").unwrap();
+    assert_eq!(
+        identities,
+        [
+            EfuseIdentity {
+                name: DEVICE_IDENTITY.to_string(),
+                block: 4,
+            },
+            EfuseIdentity {
+                name: "test".to_string(),
+                block: 5,
+            },
+            EfuseIdentity {
+                name: "attestation".to_string(),
+                block: 3,
+            },
+        ]
+    );
+    assert_eq!(find_efuse_identity(&identities, "test").unwrap().block, 5);
+    assert!(find_efuse_identity(&identities, "prod").is_err());
+    assert_eq!(parse_efuse_identities(4, "", "").unwrap().len(), 1);
+}
+
+#[test]
+fn rejects_bad_identities() {
+    for (device_block, extra, reserved) in [
+        // Reserved for flash encryption or secure boot.
+        (4, "", "4"),
+        (4, "test:0", "0,1"),
+        // Shared blocks and names.
+        (4, "test:4", ""),
+        (4, "test:5;other:5", ""),
+        (4, "device:5", ""),
+        (4, "test:5;test:3", ""),
+        // Malformed.
+        (6, "", ""),
+        (4, "test:6", ""),
+        (4, "test", ""),
+        (4, ":5", ""),
+        (4, "a b:5", ""),
+        (4, "", "KEY9"),
+    ] {
+        assert!(
+            parse_efuse_identities(device_block, extra, reserved).is_err(),
+            "{} {:?} {:?}",
+            device_block,
+            extra,
+            reserved
+        );
+    }
+}
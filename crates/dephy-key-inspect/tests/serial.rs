@@ -0,0 +1,252 @@
+use dephy_key_inspect::*;
+use dephy_message::{get_eth_address_bytes, personal_sign, SoftwareSigner};
+use k256::SecretKey;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+const KEY: [u8; 32] = [0x11; 32];
+const TEST_KEY: [u8; 32] = [0x22; 32];
+const CHALLENGE: [u8; 32] = [0x5a; 32];
+
+/// A device in key inspect mode over simulated eFuse.
+struct TestDevice {
+    efuse: SimulatedEfuse,
+    identities: Vec<EfuseIdentity>,
+}
+
+impl TestDevice {
+    fn new() -> Self {
+        let mut efuse = SimulatedEfuse::new();
+        efuse.write_key(4, &KEY).unwrap();
+        efuse.write_key(5, &TEST_KEY).unwrap();
+        Self {
+            efuse,
+            identities: parse_efuse_identities(4, "test:5;spare:3", "").unwrap(),
+        }
+    }
+
+    fn secret_key(&self, name: &str) -> anyhow::Result<(&EfuseIdentity, SecretKey)> {
+        let identity = find_efuse_identity(&self.identities, name)?;
+        let key = read_key_block(&self.efuse, identity.block)?.ok_or(anyhow::anyhow!(
+            "Key of identity {} not generated yet!",
+            name
+        ))?;
+        Ok((
+            identity,
+            SecretKey::from_slice(&key).map_err(|e| anyhow::anyhow!("{}", e))?,
+        ))
+    }
+}
+
+impl SerialDevice for TestDevice {
+    fn identity(&self, name: &str) -> anyhow::Result<IdentityInfo> {
+        let (identity, key) = self.secret_key(name)?;
+        let key = key.public_key();
+        Ok(IdentityInfo {
+            name: identity.name.clone(),
+            block: identity.block,
+            public_key: key.to_sec1_bytes().to_vec(),
+            address: get_eth_address_bytes(&key.into()),
+        })
+    }
+
+    fn sign_challenge(&self, name: &str, challenge: &[u8]) -> anyhow::Result<[u8; 65]> {
+        let (_, key) = self.secret_key(name)?;
+        personal_sign(
+            &SoftwareSigner::new(key),
+            challenge_message(challenge).as_bytes(),
+        )
+    }
+
+    fn firmware_info(&self) -> FirmwareInfo {
+        FirmwareInfo {
+            name: "dephy-esp32c3-rust-boilerplate".to_string(),
+            version: "0.1.0".to_string(),
+            device_name: "test".to_string(),
+            status: KeyInspectStatus::Init.name().to_string(),
+            progress: 0,
+        }
+    }
+}
+
+/// The console UART of the device, with log output around every response.
+struct Console {
+    device: TestDevice,
+    decoder: FrameDecoder,
+    rx: VecDeque<u8>,
+}
+
+impl Console {
+    fn new(device: TestDevice) -> Self {
+        let mut rx = VecDeque::new();
+        rx.extend(b"I (312) dephy_esp32c3_rust_boilerplate: Key inspect mode\n");
+        Self {
+            device,
+            decoder: FrameDecoder::new(),
+            rx,
+        }
+    }
+}
+
+impl Write for Console {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &b in buf {
+            let Some(packet) = self.decoder.push(b) else {
+                continue;
+            };
+            if let Some(res) = handle_serial_packet(&self.device, &packet) {
+                // A stale response to another request, which must be skipped.
+                self.rx
+                    .extend(SerialResponse::Error("stale".to_string()).encode(0xee));
+                self.rx
+                    .extend(b"W (1000) key_inspect: Waiting for entropy: 3%\n");
+                self.rx.extend(res);
+                self.rx.extend(b"I (2000) key_inspect: ");
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for Console {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.rx.len()).min(7);
+        for b in buf[..len].iter_mut() {
+            *b = self.rx.pop_front().unwrap();
+        }
+        Ok(len)
+    }
+}
+
+#[test]
+fn cobs_vectors() {
+    let vectors: [(&[u8], &[u8]); 6] = [
+        (&[], &[0x01]),
+        (&[0x00], &[0x01, 0x01]),
+        (&[0x00, 0x00], &[0x01, 0x01, 0x01]),
+        (&[0x11, 0x22, 0x00, 0x33], &[0x03, 0x11, 0x22, 0x02, 0x33]),
+        (&[0x11, 0x22, 0x33, 0x44], &[0x05, 0x11, 0x22, 0x33, 0x44]),
+        (&[0x11, 0x00, 0x00, 0x00], &[0x02, 0x11, 0x01, 0x01, 0x01]),
+    ];
+    for (data, encoded) in vectors {
+        assert_eq!(cobs_encode(data), encoded);
+        assert_eq!(cobs_decode(encoded).unwrap(), data);
+    }
+
+    // Vectors of 254 and 255 bytes from the COBS paper.
+    let run = |from: u8, to: u8| (from..=to).collect::<Vec<_>>();
+    let long_vectors = [
+        (run(0x01, 0xfe), [&[0xff], &run(0x01, 0xfe)[..]].concat()),
+        (
+            [&[0x00], &run(0x01, 0xfe)[..]].concat(),
+            [&[0x01, 0xff], &run(0x01, 0xfe)[..]].concat(),
+        ),
+        (
+            run(0x01, 0xff),
+            [&[0xff], &run(0x01, 0xfe)[..], &[0x02, 0xff]].concat(),
+        ),
+        (
+            [&run(0x02, 0xff)[..], &[0x00]].concat(),
+            [&[0xff], &run(0x02, 0xff)[..], &[0x01, 0x01]].concat(),
+        ),
+        (
+            [&run(0x03, 0xff)[..], &[0x00, 0x01]].concat(),
+            [&[0xfe], &run(0x03, 0xff)[..], &[0x02, 0x01]].concat(),
+        ),
+    ];
+    for (data, encoded) in long_vectors {
+        assert_eq!(cobs_encode(&data), encoded);
+        assert_eq!(cobs_decode(&encoded).unwrap(), data);
+    }
+
+    let long = (1..=255u8).cycle().take(600).collect::<Vec<_>>();
+    let encoded = cobs_encode(&long);
+    assert!(!encoded.contains(&0));
+    assert_eq!(cobs_decode(&encoded).unwrap(), long);
+
+    assert!(cobs_decode(&[0x05, 0x11]).is_err());
+    assert!(cobs_decode(&[0x02, 0x00]).is_err());
+}
+
+#[test]
+fn frames_skip_log_output() {
+    let req = SerialRequest::SignChallenge {
+        identity: "test".to_string(),
+        challenge: CHALLENGE.to_vec(),
+    };
+    let mut bytes = b"I (10) boot: ESP-IDF v5.1\n".to_vec();
+    bytes.extend(req.encode(7));
+    bytes.extend(b"log without end");
+    bytes.extend(SerialRequest::GetFirmwareInfo.encode(8));
+
+    let mut decoder = FrameDecoder::new();
+    let packets = bytes
+        .into_iter()
+        .filter_map(|b| decoder.push(b))
+        .filter_map(|p| SerialRequest::decode(&p).ok())
+        .collect::<Vec<_>>();
+    assert_eq!(packets, vec![(7, req), (8, SerialRequest::GetFirmwareInfo)]);
+
+    // A corrupted packet fails its CRC.
+    let mut bytes = SerialRequest::GetFirmwareInfo.encode(1);
+    bytes[4] ^= 0x01;
+    let packet = bytes.into_iter().find_map(|b| decoder.push(b)).unwrap();
+    assert!(SerialRequest::decode(&packet).is_err());
+    assert_eq!(handle_serial_packet(&TestDevice::new(), &packet), None);
+
+    // Overlong frames are dropped without growing the buffer.
+    assert!((0..4096).all(|_| decoder.push(0x01).is_none()));
+    assert_eq!(decoder.push(0), None);
+}
+
+#[test]
+fn client_verifies_identities() {
+    let device = TestDevice::new();
+    let mut client = SerialClient::new(Console::new(device));
+
+    let info = client.get_firmware_info().unwrap();
+    assert_eq!(info.device_name, "test");
+    assert_eq!(info.status, "init");
+
+    let device_key = SecretKey::from_slice(&KEY).unwrap().public_key();
+    let identity = client.verify_identity(DEVICE_IDENTITY, &CHALLENGE).unwrap();
+    assert_eq!(identity.block, 4);
+    assert_eq!(identity.public_key, device_key.to_sec1_bytes().to_vec());
+    assert_eq!(identity.address, get_eth_address_bytes(&device_key.into()));
+
+    let test = client.verify_identity("test", &CHALLENGE).unwrap();
+    assert_eq!(test.block, 5);
+
+    // The signature is bound to its identity and challenge.
+    let signature = client.sign_challenge("test", &CHALLENGE).unwrap();
+    assert!(verify_challenge_signature(&CHALLENGE, &signature, &test.address).is_ok());
+    assert!(verify_challenge_signature(&CHALLENGE, &signature, &identity.address).is_err());
+    assert!(verify_challenge_signature(&[0x5b; 32], &signature, &test.address).is_err());
+}
+
+#[test]
+fn client_reports_errors_and_stays_in_sync() {
+    let mut client = SerialClient::new(Console::new(TestDevice::new()));
+
+    let e = client.get_identity("spare").unwrap_err().to_string();
+    assert!(e.contains("not generated yet"), "{}", e);
+    let e = client.get_identity("unknown").unwrap_err().to_string();
+    assert!(e.contains("Unknown eFuse identity"), "{}", e);
+    let e = client
+        .sign_challenge("test", &[0x01; 8])
+        .unwrap_err()
+        .to_string();
+    assert!(e.contains("Bad challenge length"), "{}", e);
+
+    // Still in sync after errors.
+    assert_eq!(client.get_identity("test").unwrap().block, 5);
+
+    // Reading past the end of the console fails instead of blocking.
+    let mut client = SerialClient::new(std::io::Cursor::new(Vec::new()));
+    let e = client.get_firmware_info().unwrap_err().to_string();
+    assert!(e.contains("closed"), "{}", e);
+}
@@ -0,0 +1,212 @@
+use dephy_key_inspect::*;
+use dephy_message::get_eth_address;
+use k256::SecretKey;
+
+const KEY_BLOCK: usize = 4;
+
+const POLICY: EntropyWaitPolicy = EntropyWaitPolicy {
+    max_wait_secs: 3600,
+    min_wait_secs: 3,
+    min_healthy_samples: 4096,
+};
+
+/// xorshift64*, a stand-in for the hardware RNG.
+struct TestRng(u64);
+
+impl EntropySource for TestRng {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for b in buf.iter_mut() {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            *b = (self.0.wrapping_mul(0x2545f4914f6cdd1d) >> 56) as u8;
+        }
+    }
+}
+
+/// A broken RNG stuck at one value.
+struct StuckRng;
+
+impl EntropySource for StuckRng {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        buf.fill(0x42);
+    }
+}
+
+fn inspect<R: EntropySource>(efuse: SimulatedEfuse, rng: R) -> KeyInspect<SimulatedEfuse, R> {
+    let identities = parse_efuse_identities(KEY_BLOCK, "", "").unwrap();
+    KeyInspect::new(efuse, rng, EntropyHealth::default(), POLICY, identities)
+}
+
+/// Runs at most `ticks` ticks, until the key is taken.
+fn run<R: EntropySource>(
+    inspect: &mut KeyInspect<SimulatedEfuse, R>,
+    ticks: usize,
+) -> Vec<KeyInspectStatus> {
+    let mut ret = vec![KeyInspectStatus::Init];
+    for _ in 0..ticks {
+        let s = inspect.next(ret.last().unwrap().clone()).unwrap();
+        ret.push(s);
+        if matches!(ret.last(), Some(KeyInspectStatus::KeyTaken { .. })) {
+            break;
+        }
+    }
+    ret
+}
+
+#[test]
+fn generates_and_burns_the_key() {
+    let mut inspect = inspect(SimulatedEfuse::new(), TestRng(0x9e3779b97f4a7c15));
+    let statuses = run(&mut inspect, 100);
+    assert_eq!(
+        statuses[..6],
+        [
+            KeyInspectStatus::Init,
+            KeyInspectStatus::WaitingForEntropy { secs_waited: 1 },
+            KeyInspectStatus::WaitingForEntropy { secs_waited: 2 },
+            KeyInspectStatus::WaitingForEntropy { secs_waited: 3 },
+            KeyInspectStatus::WaitingForEntropy { secs_waited: 4 },
+            KeyInspectStatus::ShouldGenerateKey { secs_waited: 4 },
+        ]
+    );
+
+    let KeyInspectStatus::KeyTaken {
+        addr_hex,
+        secs_waited,
+        ..
+    } = statuses.last().unwrap().clone()
+    else {
+        panic!("Key not taken: {:?}", statuses.last());
+    };
+    assert_eq!(secs_waited, 0);
+    assert!(inspect.efuse.is_write_protected(KEY_BLOCK));
+    let key = inspect.get_key().unwrap().unwrap();
+    let key = SecretKey::from_slice(&key).unwrap().public_key();
+    assert_eq!(addr_hex, get_eth_address(&key.into()));
+
+    // Booting again finds the same key.
+    let mut again = self::inspect(inspect.efuse.clone(), StuckRng);
+    let s = again.next(KeyInspectStatus::Init).unwrap();
+    assert!(matches!(s, KeyInspectStatus::KeyTaken { addr_hex: a, .. } if a == addr_hex));
+    assert_eq!(again.health.samples, 0);
+}
+
+#[test]
+fn stuck_rng_never_burns_a_key() {
+    let mut inspect = inspect(SimulatedEfuse::new(), StuckRng);
+    let statuses = run(&mut inspect, 4000);
+    assert_eq!(
+        statuses.last(),
+        Some(&KeyInspectStatus::WaitingForEntropy { secs_waited: 4000 })
+    );
+    assert!(inspect.health.rct_failures > 0);
+    assert_eq!(inspect.efuse, SimulatedEfuse::new());
+}
+
+#[test]
+fn used_key_block_is_never_burnt_again() {
+    // Programmed bits which are not a valid secret key.
+    let mut efuse = SimulatedEfuse::new();
+    efuse.program(KEY_BLOCK, &[0xff; 32]).unwrap();
+    let mut inspect = inspect(efuse.clone(), TestRng(1));
+    assert!(inspect.next(KeyInspectStatus::Init).is_err());
+
+    let mut buf = [0; ENTROPY_SAMPLE_BYTES];
+    inspect.rng.fill_bytes(&mut buf);
+    assert!(inspect.health.feed(&buf));
+    assert!(inspect
+        .next(KeyInspectStatus::ShouldGenerateKey { secs_waited: 0 })
+        .is_err());
+    assert_eq!(inspect.efuse, efuse);
+
+    // Write protected blocks read as used, even without programmed bits.
+    let mut efuse = SimulatedEfuse::new();
+    efuse.set_write_protect(KEY_BLOCK).unwrap();
+    let mut inspect = self::inspect(efuse, TestRng(1));
+    assert!(inspect.next(KeyInspectStatus::Init).is_err());
+}
+
+#[test]
+fn generates_extra_identities_only_on_request() {
+    let identities = parse_efuse_identities(KEY_BLOCK, "test:5", "0,1").unwrap();
+    let mut efuse = SimulatedEfuse::new();
+    efuse.write_key(KEY_BLOCK, &[0x11; 32]).unwrap();
+    let mut inspect = KeyInspect::new(
+        efuse.clone(),
+        TestRng(7),
+        EntropyHealth::default(),
+        POLICY,
+        identities,
+    );
+    let test = EfuseIdentity {
+        name: "test".to_string(),
+        block: 5,
+    };
+    assert_eq!(
+        inspect.missing_identities().unwrap(),
+        std::slice::from_ref(&test)
+    );
+    assert!(inspect.identities_to_generate().unwrap().is_empty());
+
+    // The device key is there, the missing test key alone burns nothing.
+    assert!(matches!(
+        inspect.next(KeyInspectStatus::Init).unwrap(),
+        KeyInspectStatus::KeyTaken { .. }
+    ));
+    assert!(inspect
+        .next(KeyInspectStatus::ShouldGenerateKey { secs_waited: 0 })
+        .is_ok());
+    assert_eq!(inspect.efuse, efuse);
+
+    inspect.provision_extra_identities = true;
+    assert_eq!(inspect.identities_to_generate().unwrap(), [test]);
+    let statuses = run(&mut inspect, 100);
+    assert_eq!(
+        statuses[1],
+        KeyInspectStatus::WaitingForEntropy { secs_waited: 1 }
+    );
+    assert!(matches!(
+        statuses.last(),
+        Some(KeyInspectStatus::KeyTaken { .. })
+    ));
+    assert!(inspect.missing_identities().unwrap().is_empty());
+    assert_eq!(inspect.get_key().unwrap(), Some([0x11; 32]));
+
+    let addresses = inspect.identity_addresses().unwrap();
+    assert_eq!(addresses.len(), 2);
+    assert_eq!(addresses[0].0.name, DEVICE_IDENTITY);
+    assert_eq!(addresses[1].0.block, 5);
+    assert_ne!(addresses[0].1, addresses[1].1);
+}
+
+#[test]
+fn blank_device_gets_only_the_device_key() {
+    let identities = parse_efuse_identities(KEY_BLOCK, "test:5", "0,1").unwrap();
+    let mut inspect = KeyInspect::new(
+        SimulatedEfuse::new(),
+        TestRng(7),
+        EntropyHealth::default(),
+        POLICY,
+        identities,
+    );
+    assert!(matches!(
+        run(&mut inspect, 100).last(),
+        Some(KeyInspectStatus::KeyTaken { .. })
+    ));
+    assert!(inspect.get_key().unwrap().is_some());
+    assert_eq!(inspect.missing_identities().unwrap().len(), 1);
+    assert!(inspect.efuse.key_block_unused(5));
+}
+
+#[test]
+fn refuses_key_blocks_of_other_purposes() {
+    let mut efuse = SimulatedEfuse::new();
+    // Burnt for flash encryption (XTS_AES_128_KEY).
+    efuse.set_key_purpose(KEY_BLOCK, 4).unwrap();
+    let mut inspect = inspect(efuse.clone(), TestRng(1));
+    assert!(inspect.next(KeyInspectStatus::Init).is_err());
+    assert!(inspect
+        .next(KeyInspectStatus::ShouldGenerateKey { secs_waited: 0 })
+        .is_err());
+    assert_eq!(inspect.efuse, efuse);
+}
@@ -0,0 +1,50 @@
+use dephy_key_inspect::*;
+
+const POLICY: EntropyWaitPolicy = EntropyWaitPolicy {
+    max_wait_secs: 3600,
+    min_wait_secs: 60,
+    min_healthy_samples: 64 * 1024,
+};
+
+/// Healthy output of `len` bytes without repeats in a row.
+fn healthy(len: usize) -> EntropyHealth {
+    let mut health = EntropyHealth::default();
+    let data: Vec<u8> = (0..len).map(|i| (i * 7 % 251) as u8).collect();
+    assert!(health.feed(&data));
+    health
+}
+
+#[test]
+fn finishes_early_with_enough_healthy_samples() {
+    let health = healthy(64 * 1024);
+    assert!(!POLICY.is_done(59, &health));
+    assert_eq!(POLICY.progress(30, &health), 50);
+    assert!(POLICY.is_done(60, &health));
+    assert_eq!(POLICY.progress(60, &health), 100);
+
+    let health = healthy(16 * 1024);
+    assert!(!POLICY.is_done(600, &health));
+    assert_eq!(POLICY.progress(600, &health), 25);
+}
+
+#[test]
+fn waits_the_maximum_without_early_exit() {
+    let policy = EntropyWaitPolicy {
+        min_healthy_samples: 0,
+        ..POLICY
+    };
+    let health = healthy(1024 * 1024);
+    assert!(!policy.is_done(3599, &health));
+    assert_eq!(policy.progress(1800, &health), 50);
+    assert_eq!(policy.progress(3599, &health), 99);
+    assert!(policy.is_done(3600, &health));
+}
+
+#[test]
+fn never_done_while_unhealthy() {
+    let mut health = healthy(64 * 1024);
+    health.feed(&[0; 16]);
+    assert!(!health.is_healthy());
+    assert!(!POLICY.is_done(7200, &health));
+    assert_eq!(POLICY.progress(7200, &health), 99);
+}
@@ -0,0 +1,11 @@
+use std::env;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Fall back to the vendored protoc so the crate builds without any system package.
+    if env::var_os("PROTOC").is_none() {
+        env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+    prost_build::compile_protos(&["proto/message.proto"], &["proto/"])?;
+    println!("cargo:rerun-if-changed=proto/message.proto");
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::crypto::did_str_to_addr_bytes;
+use crate::did::addr_bytes_to_did_str;
+use crate::jwt::{sign_jwt_es256k, Jwt};
+use crate::preludes::*;
+use crate::signer::Signer;
+use serde_json::{json, Value};
+
+/// Claims of a verified device bearer token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthToken {
+    /// Address of the `did:dephy` identity that signed the token.
+    pub issuer: [u8; 20],
+    pub audience: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+    /// Hex-encoded `jti`, unique for every token.
+    pub nonce: String,
+}
+
+/// Signs a short-lived ES256K JWT authenticating `signer` to `audience`, to be sent in
+/// `Authorization: Bearer <token>`.
+///
+/// `nonce` should be freshly generated for every token, so that backends can reject
+/// replayed tokens.
+pub fn sign_auth_token<S: Signer>(
+    signer: &S,
+    audience: &str,
+    issued_at: u64,
+    lifetime: u64,
+    nonce: &[u8; 16],
+) -> Result<String> {
+    let did = addr_bytes_to_did_str(&signer.address_bytes());
+    let claims = json!({
+        "iss": did,
+        "sub": did,
+        "aud": audience,
+        "iat": issued_at,
+        "exp": issued_at + lifetime,
+        "jti": hex::encode(nonce),
+    });
+    let header = json!({
+        "typ": "JWT",
+        "kid": format!("{}#key-1", did),
+    });
+    sign_jwt_es256k(signer, header, &claims)
+}
+
+/// Verifies a device bearer token issued for `audience`, and that it is valid at `now`
+/// allowing a clock skew of `leeway` seconds.
+///
+/// Checking that the nonce is not reused until the token expires is up to the caller.
+pub fn check_auth_token(token: &str, audience: &str, now: u64, leeway: u64) -> Result<AuthToken> {
+    let jwt = Jwt::decode(token)?;
+    let issuer = jwt
+        .claims
+        .get("iss")
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("Token without issuer!"))?;
+    let issuer: [u8; 20] = did_str_to_addr_bytes(issuer)?
+        .try_into()
+        .map_err(|_| anyhow!("Bad issuer DID!"))?;
+    jwt.verify_es256k_address(&issuer)?;
+
+    let audiences = match jwt.claims.get("aud") {
+        Some(Value::String(aud)) => vec![aud.as_str()],
+        Some(Value::Array(aud)) => aud.iter().filter_map(Value::as_str).collect(),
+        _ => bail!("Token without audience!"),
+    };
+    ensure!(
+        audiences.contains(&audience),
+        "Token is not issued for {}: aud={:?}",
+        audience,
+        audiences
+    );
+    jwt.check_time(now, leeway)?;
+
+    Ok(AuthToken {
+        issuer,
+        audience: audience.to_string(),
+        issued_at: jwt
+            .numeric_date("iat")?
+            .ok_or(anyhow!("Token without iat!"))?,
+        expires_at: jwt
+            .numeric_date("exp")?
+            .ok_or(anyhow!("Token without exp!"))?,
+        nonce: jwt
+            .claims
+            .get("jti")
+            .and_then(Value::as_str)
+            .filter(|jti| !jti.is_empty())
+            .ok_or(anyhow!("Token without jti!"))?
+            .to_string(),
+    })
+}
+
+/// Token in the value of an `Authorization` header with the `Bearer` scheme.
+pub fn parse_bearer(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("Bearer") {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
+
+/// Default audience of tokens sent to `url`: its origin, like `https://example.com:8443`.
+pub fn url_audience(url: &str) -> Result<String> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or(anyhow!("Bad URL without scheme: {}", url))?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    ensure!(!host.is_empty(), "Bad URL without host: {}", url);
+    Ok(format!(
+        "{}://{}",
+        scheme.to_ascii_lowercase(),
+        host.to_ascii_lowercase()
+    ))
+}
@@ -0,0 +1,232 @@
+//! Minimal CBOR (RFC 8949) codec, enough for COSE messages.
+//!
+//! Only definite lengths are supported, and integers are encoded in the shortest form.
+
+use crate::preludes::*;
+use alloc::boxed::Box;
+
+/// Maximum nesting of arrays, maps and tags accepted when decoding.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CborValue {
+    Unsigned(u64),
+    /// Negative integer `-1 - n`.
+    Negative(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<CborValue>),
+    Map(Vec<(CborValue, CborValue)>),
+    Tag(u64, Box<CborValue>),
+    Bool(bool),
+    Null,
+}
+
+fn encode_head(major: u8, value: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+impl CborValue {
+    pub fn int(value: i64) -> Self {
+        if value < 0 {
+            CborValue::Negative(!value as u64)
+        } else {
+            CborValue::Unsigned(value as u64)
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut ret = vec![];
+        self.encode_to(&mut ret);
+        ret
+    }
+
+    pub fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            CborValue::Unsigned(v) => encode_head(0, *v, out),
+            CborValue::Negative(v) => encode_head(1, *v, out),
+            CborValue::Bytes(b) => {
+                encode_head(2, b.len() as u64, out);
+                out.extend_from_slice(b);
+            }
+            CborValue::Text(s) => {
+                encode_head(3, s.len() as u64, out);
+                out.extend_from_slice(s.as_bytes());
+            }
+            CborValue::Array(items) => {
+                encode_head(4, items.len() as u64, out);
+                for i in items {
+                    i.encode_to(out);
+                }
+            }
+            CborValue::Map(entries) => {
+                encode_head(5, entries.len() as u64, out);
+                for (k, v) in entries {
+                    k.encode_to(out);
+                    v.encode_to(out);
+                }
+            }
+            CborValue::Tag(tag, v) => {
+                encode_head(6, *tag, out);
+                v.encode_to(out);
+            }
+            CborValue::Bool(false) => out.push(0xf4),
+            CborValue::Bool(true) => out.push(0xf5),
+            CborValue::Null => out.push(0xf6),
+        }
+    }
+
+    /// Decodes a single item which must span the whole `data`.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut decoder = Decoder { data, pos: 0 };
+        let ret = decoder.item(0)?;
+        ensure!(
+            decoder.pos == data.len(),
+            "Trailing {} bytes after CBOR item!",
+            data.len() - decoder.pos
+        );
+        Ok(ret)
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            CborValue::Unsigned(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            CborValue::Unsigned(v) => i64::try_from(*v).ok(),
+            CborValue::Negative(v) => i64::try_from(*v).ok().map(|v| !v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            CborValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            CborValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Value of the integer `key` in a map.
+    pub fn get(&self, key: i64) -> Option<&CborValue> {
+        match self {
+            CborValue::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_i64() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(
+            self.data.len() - self.pos >= len,
+            "Truncated CBOR item at offset {}!",
+            self.pos
+        );
+        let ret = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(ret)
+    }
+
+    fn head(&mut self) -> Result<(u8, u8, u64)> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let value = match info {
+            0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            _ => bail!("Unsupported CBOR additional info: {}", info),
+        };
+        Ok((major, info, value))
+    }
+
+    fn len(&mut self, value: u64) -> Result<usize> {
+        let len = usize::try_from(value).map_err(|_| anyhow!("CBOR length overflow!"))?;
+        // Every item takes at least one byte, which bounds allocations by the input size.
+        ensure!(
+            len <= self.data.len() - self.pos,
+            "Truncated CBOR item at offset {}!",
+            self.pos
+        );
+        Ok(len)
+    }
+
+    fn item(&mut self, depth: usize) -> Result<CborValue> {
+        ensure!(depth < MAX_DEPTH, "CBOR item nested too deep!");
+        let (major, info, value) = self.head()?;
+        Ok(match major {
+            0 => CborValue::Unsigned(value),
+            1 => CborValue::Negative(value),
+            2 => {
+                let len = self.len(value)?;
+                CborValue::Bytes(self.take(len)?.to_vec())
+            }
+            3 => {
+                let len = self.len(value)?;
+                let s = core::str::from_utf8(self.take(len)?)
+                    .map_err(|e| anyhow!("Bad CBOR text: {}", e))?;
+                CborValue::Text(s.to_string())
+            }
+            4 => {
+                let len = self.len(value)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.item(depth + 1)?);
+                }
+                CborValue::Array(items)
+            }
+            5 => {
+                let len = self.len(value)?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let k = self.item(depth + 1)?;
+                    let v = self.item(depth + 1)?;
+                    entries.push((k, v));
+                }
+                CborValue::Map(entries)
+            }
+            6 => CborValue::Tag(value, Box::new(self.item(depth + 1)?)),
+            _ => match info {
+                20 => CborValue::Bool(false),
+                21 => CborValue::Bool(true),
+                22 => CborValue::Null,
+                _ => bail!("Unsupported CBOR simple value: {}", info),
+            },
+        })
+    }
+}
@@ -0,0 +1,230 @@
+//! COSE_Sign1 (RFC 9052) envelope of DePHY messages, signed with ES256K (RFC 8812).
+//!
+//! The payload is a CBOR map of the `RawMessage` fields keyed by their protobuf field
+//! numbers, plus the nonce, W3bstream options are not supported:
+//!
+//! | Key | Field          | Type   |
+//! |-----|----------------|--------|
+//! | 1   | `timestamp`    | uint   |
+//! | 2   | `from_address` | bstr   |
+//! | 3   | `to_address`   | bstr   |
+//! | 4   | `encrypted`    | bool   |
+//! | 5   | `payload`      | bstr   |
+//! | 6   | `iv`           | bstr   |
+//! | 8   | `nonce`        | uint   |
+
+use crate::cbor::CborValue;
+use crate::crypto::verify_es256k_address;
+use crate::message::decrypt_checked_payload;
+use crate::preludes::*;
+use crate::signer::Signer;
+use alloc::boxed::Box;
+use k256::ecdsa::VerifyingKey;
+use sha2::{Digest, Sha256};
+
+/// COSE algorithm of ECDSA over secp256k1 with SHA-256.
+pub static COSE_ALG_ES256K: i64 = -47;
+/// CBOR tag of COSE_Sign1 messages.
+pub static COSE_SIGN1_TAG: u64 = 18;
+/// Content type of COSE_Sign1 messages over HTTP.
+pub static CONTENT_TYPE_COSE_SIGN1: &str = "application/cose; cose-type=\"cose-sign1\"";
+
+const HEADER_ALG: i64 = 1;
+const HEADER_KID: i64 = 4;
+
+const KEY_TIMESTAMP: i64 = 1;
+const KEY_FROM_ADDRESS: i64 = 2;
+const KEY_TO_ADDRESS: i64 = 3;
+const KEY_ENCRYPTED: i64 = 4;
+const KEY_PAYLOAD: i64 = 5;
+const KEY_IV: i64 = 6;
+const KEY_NONCE: i64 = 8;
+
+fn encode_payload(raw: &RawMessage, nonce: u64) -> Vec<u8> {
+    let mut entries = vec![
+        (
+            CborValue::int(KEY_TIMESTAMP),
+            CborValue::Unsigned(raw.timestamp),
+        ),
+        (
+            CborValue::int(KEY_FROM_ADDRESS),
+            CborValue::Bytes(raw.from_address.clone()),
+        ),
+        (
+            CborValue::int(KEY_TO_ADDRESS),
+            CborValue::Bytes(raw.to_address.clone()),
+        ),
+        (
+            CborValue::int(KEY_ENCRYPTED),
+            CborValue::Bool(raw.encrypted),
+        ),
+        (
+            CborValue::int(KEY_PAYLOAD),
+            CborValue::Bytes(raw.payload.clone()),
+        ),
+    ];
+    if let Some(iv) = &raw.iv {
+        entries.push((CborValue::int(KEY_IV), CborValue::Bytes(iv.clone())));
+    }
+    entries.push((CborValue::int(KEY_NONCE), CborValue::Unsigned(nonce)));
+    CborValue::Map(entries).encode()
+}
+
+fn decode_payload(data: &[u8]) -> Result<(RawMessage, u64)> {
+    let map = CborValue::decode(data)?;
+    let bytes = |key: i64, name: &str| -> Result<Vec<u8>> {
+        Ok(map
+            .get(key)
+            .and_then(CborValue::as_bytes)
+            .ok_or(anyhow!("Bad or missing {} in COSE payload!", name))?
+            .to_vec())
+    };
+    let raw = RawMessage {
+        timestamp: map
+            .get(KEY_TIMESTAMP)
+            .and_then(CborValue::as_u64)
+            .ok_or(anyhow!("Bad or missing timestamp in COSE payload!"))?,
+        from_address: bytes(KEY_FROM_ADDRESS, "from_address")?,
+        to_address: bytes(KEY_TO_ADDRESS, "to_address")?,
+        encrypted: map
+            .get(KEY_ENCRYPTED)
+            .and_then(CborValue::as_bool)
+            .ok_or(anyhow!("Bad or missing encrypted in COSE payload!"))?,
+        payload: bytes(KEY_PAYLOAD, "payload")?,
+        iv: match map.get(KEY_IV) {
+            Some(_) => Some(bytes(KEY_IV, "iv")?),
+            None => None,
+        },
+        w3b: None,
+    };
+    let nonce = map
+        .get(KEY_NONCE)
+        .and_then(CborValue::as_u64)
+        .ok_or(anyhow!("Bad or missing nonce in COSE payload!"))?;
+    ensure!(
+        raw.from_address.len() == 20,
+        "Bad from_address length: {}",
+        raw.from_address.len()
+    );
+    Ok((raw, nonce))
+}
+
+/// SHA-256 of the `Sig_structure` of a COSE_Sign1 message without external AAD.
+fn sig_structure_hash(protected: &[u8], payload: &[u8]) -> [u8; 32] {
+    let sig_structure = CborValue::Array(vec![
+        CborValue::Text("Signature1".to_string()),
+        CborValue::Bytes(protected.to_vec()),
+        CborValue::Bytes(vec![]),
+        CborValue::Bytes(payload.to_vec()),
+    ]);
+    Sha256::digest(sig_structure.encode()).into()
+}
+
+/// Encodes `raw` and `nonce` as a tagged COSE_Sign1 message signed by `signer`, of which
+/// the address is the key id.
+pub(crate) fn sign_cose_message<S: Signer>(
+    signer: &S,
+    raw: &RawMessage,
+    nonce: u64,
+) -> Result<Vec<u8>> {
+    ensure!(
+        raw.w3b.is_none(),
+        "W3bstream options are not supported in COSE_Sign1 messages!"
+    );
+    let protected = CborValue::Map(vec![(
+        CborValue::int(HEADER_ALG),
+        CborValue::int(COSE_ALG_ES256K),
+    )])
+    .encode();
+    let unprotected = CborValue::Map(vec![(
+        CborValue::int(HEADER_KID),
+        CborValue::Bytes(signer.address_bytes().to_vec()),
+    )]);
+    let payload = encode_payload(raw, nonce);
+    let (signature, _) =
+        signer.sign_prehash_recoverable(&sig_structure_hash(&protected, &payload))?;
+    Ok(CborValue::Tag(
+        COSE_SIGN1_TAG,
+        Box::new(CborValue::Array(vec![
+            CborValue::Bytes(protected),
+            unprotected,
+            CborValue::Bytes(payload),
+            CborValue::Bytes(signature.to_vec()),
+        ])),
+    )
+    .encode())
+}
+
+/// Whether `data` looks like a tagged COSE_Sign1 message rather than a `SignedMessage`.
+pub fn is_cose_sign1(data: &[u8]) -> bool {
+    // Tag 18 is encoded in one byte, `SignedMessage` starts with the key of field 1.
+    data.first() == Some(&0xd2)
+}
+
+/// Verifies a COSE_Sign1 message signed by the key of `from_address`, and returns the
+/// message and its nonce.
+pub fn check_cose_message(data: &[u8]) -> Result<(RawMessage, u64)> {
+    let (raw, nonce, _) = check_cose_message_with_sender(data)?;
+    Ok((raw, nonce))
+}
+
+/// Verifies a COSE_Sign1 message and returns its payload, decrypted if it was encrypted
+/// to `signer`.
+pub fn check_and_decrypt_cose_message<S: Signer>(
+    data: &[u8],
+    signer: &S,
+) -> Result<(RawMessage, u64, Vec<u8>)> {
+    let (raw, nonce, sender) = check_cose_message_with_sender(data)?;
+    let payload = decrypt_checked_payload(&raw, &sender, signer)?;
+    Ok((raw, nonce, payload))
+}
+
+/// Decodes a COSE_Sign1 message without verifying it.
+pub fn decode_cose_message(data: &[u8]) -> Result<(RawMessage, u64)> {
+    let (_, payload, _) = split_cose_sign1(data)?;
+    decode_payload(&payload)
+}
+
+/// Protected header, payload and signature of a COSE_Sign1 message, either tagged or not.
+fn split_cose_sign1(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    let msg = match CborValue::decode(data)? {
+        CborValue::Tag(tag, msg) if tag == COSE_SIGN1_TAG => *msg,
+        CborValue::Tag(tag, _) => bail!("Not a COSE_Sign1 message, tag={}", tag),
+        msg => msg,
+    };
+    let CborValue::Array(items) = msg else {
+        bail!("Bad COSE_Sign1 message, expected an array!");
+    };
+    let Ok::<[CborValue; 4], _>(
+        [CborValue::Bytes(protected), CborValue::Map(_), CborValue::Bytes(payload), CborValue::Bytes(signature)],
+    ) = items.try_into()
+    else {
+        bail!("Bad COSE_Sign1 message, expected [protected, unprotected, payload, signature]!");
+    };
+    Ok((protected, payload, signature))
+}
+
+fn check_cose_message_with_sender(data: &[u8]) -> Result<(RawMessage, u64, VerifyingKey)> {
+    let (protected, payload, signature) = split_cose_sign1(data)?;
+    let alg = CborValue::decode(&protected)?
+        .get(HEADER_ALG)
+        .and_then(CborValue::as_i64);
+    ensure!(
+        alg == Some(COSE_ALG_ES256K),
+        "Unsupported COSE algorithm: {:?}",
+        alg
+    );
+    let (raw, nonce) = decode_payload(&payload)?;
+    let sender = verify_es256k_address(
+        &sig_structure_hash(&protected, &payload),
+        &signature,
+        &raw.from_address,
+    )?;
+    debug!(
+        "COSE_Sign1: from=0x{} nonce={} timestamp={}",
+        hex::encode(&raw.from_address),
+        nonce,
+        raw.timestamp
+    );
+    Ok((raw, nonce, sender))
+}
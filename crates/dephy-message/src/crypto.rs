@@ -0,0 +1,138 @@
+use crate::preludes::*;
+use crate::signer::Signer;
+use aes::Aes256;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{PublicKey, SecretKey};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+pub fn get_eth_address_bytes(key: &VerifyingKey) -> [u8; 20] {
+    let key = key.to_encoded_point(false);
+    let key = key.as_bytes();
+    let mut hasher = Keccak256::default();
+    hasher.update(&key[1..]);
+    let hash: [u8; 32] = hasher.finalize().into();
+    let addr = &hash[12..32];
+    addr.try_into().unwrap()
+}
+
+pub fn get_eth_address(key: &VerifyingKey) -> String {
+    format!("0x{}", hex::encode(get_eth_address_bytes(key)))
+}
+
+/// Checks a 64-byte `r || s` ECDSA signature of `prehash` against the key of `address`,
+/// and returns the key.
+///
+/// Signatures with a high S are accepted, as ES256K (RFC 8812) does not require low S.
+pub(crate) fn verify_es256k_address(
+    prehash: &[u8; 32],
+    signature: &[u8],
+    address: &[u8],
+) -> Result<VerifyingKey> {
+    let signature = Signature::try_from(signature).map_err(|e| anyhow!("Bad signature: {}", e))?;
+    let signature = signature.normalize_s().unwrap_or(signature);
+    [0u8, 1]
+        .into_iter()
+        .filter_map(|v| {
+            VerifyingKey::recover_from_prehash(prehash, &signature, RecoveryId::from_byte(v)?).ok()
+        })
+        .find(|key| get_eth_address_bytes(key).as_slice() == address)
+        .ok_or(anyhow!(
+            "Signature check failed: not signed by 0x{}",
+            hex::encode(address)
+        ))
+}
+
+/// Mixed-case checksum encoding of `addr`, see EIP-55.
+pub fn to_checksum_address(addr: &[u8; 20]) -> String {
+    let addr = hex::encode(addr);
+    let hash = Keccak256::digest(addr.as_bytes());
+    let checksummed = addr
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    format!("0x{}", checksummed)
+}
+
+pub fn did_str_to_addr_bytes<T: Into<String>>(did_str: T) -> Result<Vec<u8>> {
+    let did_str: String = did_str.into();
+    let did_str = did_str
+        .strip_prefix("did:dephy:0x")
+        .ok_or(anyhow!("Not in DID string format."))?;
+    if did_str.len() != 40 {
+        bail!("Invalid length for an DID string format.")
+    }
+    hex::decode(did_str).map_err(|e| anyhow!("Invalid DID string: {}", e))
+}
+
+/// Derives the AES-256 key shared between `secret` and `peer`.
+///
+/// Same as the default hash function of `secp256k1_ecdh` in libsecp256k1:
+/// SHA-256 over the compressed shared point.
+pub fn get_shared_key(secret: &SecretKey, peer: &PublicKey) -> [u8; 32] {
+    let shared = (peer.to_projective() * *secret.to_nonzero_scalar()).to_affine();
+    let shared = shared.to_encoded_point(true);
+    let mut hasher = Sha256::new();
+    hasher.update(shared.as_bytes());
+    hasher.finalize().into()
+}
+
+pub fn encrypt_payload<S: Signer>(
+    signer: &S,
+    peer: &PublicKey,
+    payload: &[u8],
+    iv: &[u8; 16],
+) -> Result<Vec<u8>> {
+    let key = signer.shared_key(peer)?;
+    let cipher = Aes256CbcEnc::new(&key.into(), iv.into());
+    Ok(cipher.encrypt_padded_vec_mut::<Pkcs7>(payload))
+}
+
+pub fn decrypt_payload<S: Signer>(
+    signer: &S,
+    peer: &PublicKey,
+    payload: &[u8],
+    iv: &[u8],
+) -> Result<Vec<u8>> {
+    ensure!(iv.len() == 16, "Bad IV length!");
+    let key = signer.shared_key(peer)?;
+    let cipher = Aes256CbcDec::new_from_slices(&key, iv).map_err(|e| anyhow!("Bad IV: {}", e))?;
+    cipher
+        .decrypt_padded_vec_mut::<Pkcs7>(payload)
+        .map_err(|_| anyhow!("Failed to decrypt payload: bad key or padding"))
+}
+
+/// Encodes the payload in the way W3bstream expects it to be ingested.
+pub fn encode_w3b_payload(payload: Vec<u8>, encoding: W3bstreamPayloadEncoding) -> Result<Vec<u8>> {
+    Ok(match encoding {
+        W3bstreamPayloadEncoding::WpeUtf8 => {
+            ensure!(
+                core::str::from_utf8(payload.as_slice()).is_ok(),
+                "W3bstream payload is not valid UTF-8!"
+            );
+            payload
+        }
+        W3bstreamPayloadEncoding::WpeUtf8Json => {
+            serde_json::from_slice::<serde_json::Value>(payload.as_slice())
+                .map_err(|e| anyhow!("W3bstream payload is not valid JSON: {}", e))?;
+            payload
+        }
+        W3bstreamPayloadEncoding::WpeHex => hex::encode(payload).into_bytes(),
+        W3bstreamPayloadEncoding::WpeBase64 => BASE64.encode(payload).into_bytes(),
+    })
+}
@@ -0,0 +1,73 @@
+use crate::crypto::get_eth_address_bytes;
+use crate::eth::{recover_eth_signer, sign_eth_digest};
+use crate::preludes::*;
+use crate::signer::Signer;
+use k256::ecdsa::VerifyingKey;
+use sha3::{Digest, Keccak256};
+
+/// Prefix of the data signed by delegation certificates.
+///
+/// `personal_sign`, EIP-712 and transaction digests all start with prefixes of their own, so
+/// signing data supplied by a host with them can never produce a certificate.
+pub const DELEGATION_DIGEST_PREFIX: &[u8] = b"DePHY delegation v1\n";
+
+/// Digest of `raw`, an encoded `DelegationRaw`, signed by the root key of a certificate.
+pub fn delegation_digest(raw: &[u8]) -> Keccak256 {
+    let mut hasher = Keccak256::new();
+    hasher.update(DELEGATION_DIGEST_PREFIX);
+    hasher.update(raw);
+    hasher
+}
+
+/// Certifies `session` to sign messages on behalf of `root` from `not_before` to
+/// `not_after` (both inclusive, in seconds).
+pub fn create_delegation<S: Signer>(
+    root: &S,
+    session: &VerifyingKey,
+    not_before: u64,
+    not_after: u64,
+) -> Result<DelegationCertificate> {
+    ensure!(
+        not_before <= not_after,
+        "Bad validity window: not_before={} not_after={}",
+        not_before,
+        not_after
+    );
+    let raw = DelegationRaw {
+        session_public_key: session.to_sec1_bytes().to_vec(),
+        not_before,
+        not_after,
+    }
+    .encode_to_vec();
+    let signature = sign_eth_digest(root, delegation_digest(&raw))?.to_vec();
+    Ok(DelegationCertificate { raw, signature })
+}
+
+/// Checks the signature of `cert` and that `timestamp` is in its validity window.
+///
+/// Returns the address of the root key and the certified session key.
+pub fn check_delegation(
+    cert: &DelegationCertificate,
+    timestamp: u64,
+) -> Result<([u8; 20], VerifyingKey)> {
+    let raw = DelegationRaw::decode(cert.raw.as_slice())
+        .map_err(|e| anyhow!("Bad DelegationRaw: {}", e))?;
+    let root = recover_eth_signer(delegation_digest(&cert.raw), &cert.signature)?;
+    let session = VerifyingKey::from_sec1_bytes(&raw.session_public_key)
+        .map_err(|e| anyhow!("Bad session public key: {}", e))?;
+    ensure!(
+        raw.not_before <= timestamp && timestamp <= raw.not_after,
+        "Delegation is not valid at {}: not_before={} not_after={}",
+        timestamp,
+        raw.not_before,
+        raw.not_after
+    );
+    debug!(
+        "Delegation: root=0x{} session=0x{} not_before={} not_after={}",
+        hex::encode(get_eth_address_bytes(&root)),
+        hex::encode(&raw.session_public_key),
+        raw.not_before,
+        raw.not_after
+    );
+    Ok((get_eth_address_bytes(&root), session))
+}
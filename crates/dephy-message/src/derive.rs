@@ -0,0 +1,63 @@
+use crate::preludes::*;
+use hkdf::Hkdf;
+use k256::SecretKey;
+use sha2::Sha256;
+
+static DERIVATION_SALT: &[u8] = b"DePHY key derivation";
+
+/// What a derived key is used for, each purpose gets an independent key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyPurpose {
+    Signing,
+    Encryption,
+    BlePairing,
+    TransportAuth,
+}
+
+impl KeyPurpose {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyPurpose::Signing => "dephy:signing",
+            KeyPurpose::Encryption => "dephy:encryption",
+            KeyPurpose::BlePairing => "dephy:ble-pairing",
+            KeyPurpose::TransportAuth => "dephy:transport-auth",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        [
+            KeyPurpose::Signing,
+            KeyPurpose::Encryption,
+            KeyPurpose::BlePairing,
+            KeyPurpose::TransportAuth,
+        ]
+        .into_iter()
+        .find(|p| p.label() == label)
+    }
+}
+
+/// Derives the key for `purpose` from `root` with HKDF-SHA256.
+///
+/// Derived keys can't be used to recover `root` or keys of other purposes.
+pub fn derive_key(root: &SecretKey, purpose: KeyPurpose) -> Result<SecretKey> {
+    derive_key_with_label(root, purpose.label())
+}
+
+/// Derives a key with a custom purpose label, see `derive_key`.
+pub fn derive_key_with_label(root: &SecretKey, label: &str) -> Result<SecretKey> {
+    let hkdf = Hkdf::<Sha256>::new(Some(DERIVATION_SALT), &root.to_bytes());
+    let mut info = label.as_bytes().to_vec();
+    // The output is out of the curve order with a probability of about 2^-128, in which
+    // case a counter is appended to the label.
+    for counter in 0u8..=u8::MAX {
+        let mut buf = [0u8; 32];
+        hkdf.expand(&info, &mut buf)
+            .map_err(|e| anyhow!("Failed to derive key: {}", e))?;
+        if let Ok(key) = SecretKey::from_slice(&buf) {
+            return Ok(key);
+        }
+        info.truncate(label.len());
+        info.push(counter);
+    }
+    bail!("Failed to derive key for {}", label)
+}
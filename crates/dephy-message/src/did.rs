@@ -0,0 +1,89 @@
+use crate::crypto::get_eth_address_bytes;
+use crate::preludes::*;
+use k256::ecdsa::VerifyingKey;
+use serde_json::{json, Value};
+
+/// Multicodec prefix of compressed secp256k1 public keys, `0xe7` as a varint.
+static MULTICODEC_SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+
+pub fn addr_bytes_to_did_str(addr: &[u8; 20]) -> String {
+    format!("did:dephy:0x{}", hex::encode(addr))
+}
+
+/// Service endpoint of a DID document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DidService {
+    /// Fragment of the service id, without `#`.
+    pub id: String,
+    pub service_type: String,
+    pub endpoint: String,
+}
+
+impl DidService {
+    pub fn new<I: Into<String>, T: Into<String>, E: Into<String>>(
+        id: I,
+        service_type: T,
+        endpoint: E,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            service_type: service_type.into(),
+            endpoint: endpoint.into(),
+        }
+    }
+}
+
+/// Parses services in the form of `<id>|<type>|<endpoint>`, separated by `;`.
+pub fn parse_did_services(s: &str) -> Result<Vec<DidService>> {
+    s.split(';')
+        .map(str::trim)
+        .filter(|i| !i.is_empty())
+        .map(|i| {
+            let parts = i.split('|').map(str::trim).collect::<Vec<_>>();
+            ensure!(
+                parts.len() == 3 && parts.iter().all(|p| !p.is_empty()),
+                "Bad DID service, expected <id>|<type>|<endpoint>: {}",
+                i
+            );
+            Ok(DidService::new(parts[0], parts[1], parts[2]))
+        })
+        .collect()
+}
+
+/// `publicKeyMultibase` of `key` as in the Multikey verification method type.
+pub fn public_key_multibase(key: &VerifyingKey) -> String {
+    let mut buf = MULTICODEC_SECP256K1_PUB.to_vec();
+    buf.extend_from_slice(&key.to_sec1_bytes());
+    format!("z{}", bs58::encode(buf).into_string())
+}
+
+/// W3C DID document of the `did:dephy` identity of `key`.
+pub fn did_document(key: &VerifyingKey, services: &[DidService]) -> Value {
+    let did = addr_bytes_to_did_str(&get_eth_address_bytes(key));
+    let services = services
+        .iter()
+        .map(|s| {
+            json!({
+                "id": format!("{}#{}", did, s.id),
+                "type": s.service_type,
+                "serviceEndpoint": s.endpoint,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "@context": [
+            "https://www.w3.org/ns/did/v1",
+            "https://w3id.org/security/multikey/v1",
+        ],
+        "id": did,
+        "verificationMethod": [{
+            "id": format!("{}#key-1", did),
+            "type": "Multikey",
+            "controller": did,
+            "publicKeyMultibase": public_key_multibase(key),
+        }],
+        "authentication": ["#key-1"],
+        "assertionMethod": ["#key-1"],
+        "service": services,
+    })
+}
@@ -0,0 +1,249 @@
+use crate::preludes::*;
+use crate::signer::Signer;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use sha3::{Digest, Keccak256};
+
+/// Digest of `message` as signed by `personal_sign`, see EIP-191 version `0x45`.
+pub fn eip191_digest(message: &[u8]) -> Keccak256 {
+    let mut hasher = Keccak256::new();
+    hasher.update(b"\x19Ethereum Signed Message:\n");
+    hasher.update(message.len().to_string().as_bytes());
+    hasher.update(message);
+    hasher
+}
+
+/// Digest of `message` under `domain` as signed by `eth_signTypedData_v4`, see EIP-712.
+pub fn eip712_digest(domain: &Eip712Domain, message: &Eip712Struct) -> Keccak256 {
+    let mut hasher = Keccak256::new();
+    hasher.update(b"\x19\x01");
+    hasher.update(domain.separator());
+    hasher.update(message.hash());
+    hasher
+}
+
+/// Signs `digest` and returns the signature as r || s || v, with `v` being 27 or 28 as
+/// `ecrecover` expects.
+pub fn sign_eth_digest<S: Signer>(signer: &S, digest: Keccak256) -> Result<[u8; 65]> {
+    let (signature, recid) = signer.sign_digest_recoverable(digest)?;
+    let mut ret = [0u8; 65];
+    ret[..64].copy_from_slice(&signature.to_bytes());
+    ret[64] = 27 + recid.to_byte();
+    Ok(ret)
+}
+
+/// EIP-191 `personal_sign` over arbitrary bytes.
+pub fn personal_sign<S: Signer>(signer: &S, message: &[u8]) -> Result<[u8; 65]> {
+    sign_eth_digest(signer, eip191_digest(message))
+}
+
+/// EIP-712 signature of `message` under `domain`.
+pub fn sign_typed_data<S: Signer>(
+    signer: &S,
+    domain: &Eip712Domain,
+    message: &Eip712Struct,
+) -> Result<[u8; 65]> {
+    sign_eth_digest(signer, eip712_digest(domain, message))
+}
+
+/// Recovers the key that signed `digest`, `v` of the signature can be either 0/1 or 27/28.
+pub fn recover_eth_signer(digest: Keccak256, signature: &[u8]) -> Result<VerifyingKey> {
+    ensure!(signature.len() == 65, "Bad signature length!");
+    let rs = Signature::try_from(&signature[..64]).map_err(|e| anyhow!("Bad signature: {}", e))?;
+    let v = match signature[64] {
+        v @ (27 | 28) => v - 27,
+        v => v,
+    };
+    let v = RecoveryId::try_from(v).map_err(|e| anyhow!("Bad recovery id: {}", e))?;
+    VerifyingKey::recover_from_digest(digest, &rs, v)
+        .map_err(|e| anyhow!("Failed to recover signer: {}", e))
+}
+
+/// Value of a member of an EIP-712 struct.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Eip712Value {
+    /// Atomic value already encoded into one 32-byte word, e.g. `uint256`, `address`,
+    /// `bool` and `bytes32`.
+    Word([u8; 32]),
+    /// `bytes`, encoded as its Keccak-256 hash.
+    Bytes(Vec<u8>),
+    /// `string`, encoded as the Keccak-256 hash of its UTF-8 bytes.
+    String(String),
+    Struct(Eip712Struct),
+    /// Fixed or dynamic array, of which the member type ends with `[]` or `[n]`.
+    Array(Vec<Eip712Value>),
+}
+
+impl Eip712Value {
+    pub fn uint(value: u128) -> Self {
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        Self::Word(word)
+    }
+
+    pub fn int(value: i128) -> Self {
+        let mut word = if value < 0 { [0xffu8; 32] } else { [0u8; 32] };
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        Self::Word(word)
+    }
+
+    pub fn address(address: [u8; 20]) -> Self {
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(&address);
+        Self::Word(word)
+    }
+
+    pub fn bool(value: bool) -> Self {
+        Self::uint(value as u128)
+    }
+
+    /// `bytes1` to `bytes32`, right padded with zeros.
+    pub fn fixed_bytes(value: &[u8]) -> Result<Self> {
+        ensure!(
+            !value.is_empty() && value.len() <= 32,
+            "Bad fixed bytes length: {}",
+            value.len()
+        );
+        let mut word = [0u8; 32];
+        word[..value.len()].copy_from_slice(value);
+        Ok(Self::Word(word))
+    }
+
+    fn encode(&self) -> [u8; 32] {
+        match self {
+            Self::Word(word) => *word,
+            Self::Bytes(bytes) => Keccak256::digest(bytes).into(),
+            Self::String(s) => Keccak256::digest(s.as_bytes()).into(),
+            Self::Struct(s) => s.hash(),
+            Self::Array(values) => {
+                let mut hasher = Keccak256::new();
+                for value in values {
+                    hasher.update(value.encode());
+                }
+                hasher.finalize().into()
+            }
+        }
+    }
+
+    fn collect_structs<'a>(&'a self, structs: &mut Vec<&'a Eip712Struct>) {
+        match self {
+            Self::Struct(s) => {
+                if !structs.iter().any(|i| i.name == s.name) {
+                    structs.push(s);
+                    for (_, _, value) in &s.members {
+                        value.collect_structs(structs);
+                    }
+                }
+            }
+            Self::Array(values) => values.iter().for_each(|v| v.collect_structs(structs)),
+            _ => {}
+        }
+    }
+}
+
+/// EIP-712 struct with its values, members are encoded in the order they are added.
+///
+/// Types referenced by the struct are discovered from the values, so arrays of structs
+/// must not be empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Eip712Struct {
+    name: String,
+    members: Vec<(String, String, Eip712Value)>,
+}
+
+impl Eip712Struct {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self {
+            name: name.into(),
+            members: vec![],
+        }
+    }
+
+    /// Adds a member, `ty` is its Solidity type, e.g. `uint256`, `address` or `Person[]`.
+    pub fn member<N: Into<String>, T: Into<String>>(
+        mut self,
+        name: N,
+        ty: T,
+        value: Eip712Value,
+    ) -> Self {
+        self.members.push((ty.into(), name.into(), value));
+        self
+    }
+
+    fn encode_own_type(&self) -> String {
+        let members = self
+            .members
+            .iter()
+            .map(|(ty, name, _)| format!("{} {}", ty, name))
+            .collect::<Vec<_>>();
+        format!("{}({})", self.name, members.join(","))
+    }
+
+    /// `encodeType`, the referenced struct types are appended sorted by name.
+    pub fn encode_type(&self) -> String {
+        let mut structs = vec![];
+        for (_, _, value) in &self.members {
+            value.collect_structs(&mut structs);
+        }
+        structs.retain(|s| s.name != self.name);
+        structs.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut ret = self.encode_own_type();
+        for s in structs {
+            ret.push_str(&s.encode_own_type());
+        }
+        ret
+    }
+
+    pub fn type_hash(&self) -> [u8; 32] {
+        Keccak256::digest(self.encode_type().as_bytes()).into()
+    }
+
+    /// `hashStruct`.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(self.type_hash());
+        for (_, _, value) in &self.members {
+            hasher.update(value.encode());
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// EIP-712 domain, only the fields that are set are part of the domain type.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Eip712Domain {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub chain_id: Option<u64>,
+    pub verifying_contract: Option<[u8; 20]>,
+    pub salt: Option<[u8; 32]>,
+}
+
+impl Eip712Domain {
+    pub fn to_struct(&self) -> Eip712Struct {
+        let mut ret = Eip712Struct::new("EIP712Domain");
+        if let Some(name) = &self.name {
+            ret = ret.member("name", "string", Eip712Value::String(name.clone()));
+        }
+        if let Some(version) = &self.version {
+            ret = ret.member("version", "string", Eip712Value::String(version.clone()));
+        }
+        if let Some(chain_id) = self.chain_id {
+            ret = ret.member("chainId", "uint256", Eip712Value::uint(chain_id as u128));
+        }
+        if let Some(contract) = self.verifying_contract {
+            ret = ret.member(
+                "verifyingContract",
+                "address",
+                Eip712Value::address(contract),
+            );
+        }
+        if let Some(salt) = self.salt {
+            ret = ret.member("salt", "bytes32", Eip712Value::Word(salt));
+        }
+        ret
+    }
+
+    pub fn separator(&self) -> [u8; 32] {
+        self.to_struct().hash()
+    }
+}
@@ -0,0 +1,132 @@
+use crate::crypto::verify_es256k_address;
+use crate::preludes::*;
+use crate::signer::Signer;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+use k256::ecdsa::signature::hazmat::PrehashVerifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// JWS algorithm of ECDSA over secp256k1 with SHA-256, see RFC 8812.
+pub static JWS_ALG_ES256K: &str = "ES256K";
+
+/// Signs `claims` as a compact JWT with ES256K.
+///
+/// `header` is either `null` or an object of extra header parameters, `alg` is always set.
+pub fn sign_jwt_es256k<S: Signer>(signer: &S, header: Value, claims: &Value) -> Result<String> {
+    let mut header = match header {
+        Value::Null => Map::new(),
+        Value::Object(h) => h,
+        _ => bail!("JWT header must be an object!"),
+    };
+    header.insert("alg".to_string(), JWS_ALG_ES256K.into());
+    let signing_input = format!(
+        "{}.{}",
+        BASE64URL.encode(Value::Object(header).to_string()),
+        BASE64URL.encode(claims.to_string())
+    );
+    let prehash: [u8; 32] = Sha256::digest(signing_input.as_bytes()).into();
+    let (signature, _) = signer.sign_prehash_recoverable(&prehash)?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        BASE64URL.encode(signature.to_bytes())
+    ))
+}
+
+/// Compact JWT decoded without verifying its signature.
+#[derive(Clone, Debug)]
+pub struct Jwt {
+    pub header: Value,
+    pub claims: Value,
+    pub signature: Vec<u8>,
+    signing_input: String,
+}
+
+impl Jwt {
+    pub fn decode(token: &str) -> Result<Self> {
+        let mut parts = token.trim().split('.');
+        let (Some(header), Some(claims), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Bad JWT, expected <header>.<claims>.<signature>!");
+        };
+        fn decode_json(part: &str, name: &str) -> Result<Value> {
+            let buf = BASE64URL
+                .decode(part)
+                .map_err(|e| anyhow!("Bad JWT {}: {}", name, e))?;
+            serde_json::from_slice(&buf).map_err(|e| anyhow!("Bad JWT {}: {}", name, e))
+        }
+        Ok(Self {
+            header: decode_json(header, "header")?,
+            claims: decode_json(claims, "claims")?,
+            signature: BASE64URL
+                .decode(signature)
+                .map_err(|e| anyhow!("Bad JWT signature: {}", e))?,
+            signing_input: format!("{}.{}", header, claims),
+        })
+    }
+
+    fn check_alg(&self) -> Result<()> {
+        ensure!(
+            self.header.get("alg").and_then(Value::as_str) == Some(JWS_ALG_ES256K),
+            "Unsupported JWT alg: {}",
+            self.header.get("alg").unwrap_or(&Value::Null)
+        );
+        Ok(())
+    }
+
+    /// Checks the ES256K signature with `key`.
+    pub fn verify_es256k(&self, key: &VerifyingKey) -> Result<()> {
+        self.check_alg()?;
+        let signature = Signature::try_from(self.signature.as_slice())
+            .map_err(|e| anyhow!("Bad JWT signature: {}", e))?;
+        // RFC 8812 doesn't require low-S signatures, which k256 only accepts.
+        let signature = signature.normalize_s().unwrap_or(signature);
+        let prehash: [u8; 32] = Sha256::digest(self.signing_input.as_bytes()).into();
+        key.verify_prehash(&prehash, &signature)
+            .map_err(|e| anyhow!("JWT signature check failed: {}", e))
+    }
+
+    /// Checks the ES256K signature against the key of `address`, for issuers only known by
+    /// their address like `did:dephy`.
+    ///
+    /// Returns the public key recovered from the signature.
+    pub fn verify_es256k_address(&self, address: &[u8; 20]) -> Result<VerifyingKey> {
+        self.check_alg()?;
+        let prehash = Sha256::digest(self.signing_input.as_bytes()).into();
+        verify_es256k_address(&prehash, &self.signature, address)
+    }
+
+    /// Numeric date claim, like `exp` or `nbf`.
+    pub fn numeric_date(&self, name: &str) -> Result<Option<u64>> {
+        match self.claims.get(name) {
+            None => Ok(None),
+            Some(v) => v
+                .as_u64()
+                .map(Some)
+                .ok_or(anyhow!("Bad JWT {} claim: {}", name, v)),
+        }
+    }
+
+    /// Checks `nbf` and `exp` against `now`, allowing a clock skew of `leeway` seconds.
+    pub fn check_time(&self, now: u64, leeway: u64) -> Result<()> {
+        if let Some(nbf) = self.numeric_date("nbf")? {
+            ensure!(
+                now.saturating_add(leeway) >= nbf,
+                "JWT is not valid before {}, now={}",
+                nbf,
+                now
+            );
+        }
+        if let Some(exp) = self.numeric_date("exp")? {
+            ensure!(
+                now.saturating_sub(leeway) < exp,
+                "JWT expired at {}, now={}",
+                exp,
+                now
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+//! DePHY message types and signing scheme, shared by the firmware, servers and tools.
+//!
+//! The crate is `no_std` with `alloc`, the `std` feature is enabled by default.
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod auth;
+pub mod cbor;
+pub mod cose;
+pub mod crypto;
+pub mod delegation;
+pub mod derive;
+pub mod did;
+pub mod eth;
+pub mod jwt;
+pub mod message;
+pub mod nostr;
+mod preludes;
+pub mod proto;
+pub mod replay;
+pub mod rlp;
+pub mod signer;
+pub mod siwe;
+pub mod tx;
+pub mod vc;
+
+pub use auth::*;
+pub use cbor::*;
+pub use cose::*;
+pub use crypto::*;
+pub use delegation::*;
+pub use derive::*;
+pub use did::*;
+pub use eth::*;
+pub use jwt::*;
+pub use message::*;
+pub use nostr::*;
+pub use proto::*;
+pub use replay::*;
+pub use rlp::*;
+pub use signer::*;
+pub use siwe::*;
+pub use tx::*;
+pub use vc::*;
@@ -0,0 +1,468 @@
+use crate::cose::sign_cose_message;
+use crate::crypto::{decrypt_payload, encode_w3b_payload, encrypt_payload, get_eth_address_bytes};
+use crate::delegation::check_delegation;
+use crate::preludes::*;
+use crate::signer::Signer;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use k256::schnorr;
+use k256::schnorr::signature::hazmat::PrehashVerifier;
+use k256::PublicKey;
+use sha3::{Digest, Keccak256};
+
+/// Builder of `SignedMessage`, covering every field of `RawMessage` and `SignedMessage`.
+///
+/// `timestamp` and `nonce` are required. Other unset fields default to: `from_address` the
+/// signer's address (or the root address of `delegation`), and `to_address` the recipient
+/// of `encrypt_to` or all zeros. Building fails when `from_address` is set to another
+/// address, since the message could never be verified.
+#[derive(Clone, Default)]
+pub struct MessageBuilder {
+    timestamp: Option<u64>,
+    from_address: Option<Vec<u8>>,
+    to_address: Option<Vec<u8>>,
+    encrypted: bool,
+    payload: Vec<u8>,
+    iv: Option<Vec<u8>>,
+    w3b: Option<W3bstreamOptions>,
+    nonce: Option<u64>,
+    last_edge_addr: Option<Vec<u8>>,
+    encrypt_to: Option<(PublicKey, [u8; 16])>,
+    schnorr_aux_rand: Option<[u8; 32]>,
+    delegation: Option<DelegationCertificate>,
+}
+
+/// How `check_message` treats messages signed by a delegated session key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DelegationMode {
+    /// Only messages signed by the key of `from_address` are accepted.
+    #[default]
+    Reject,
+    /// Messages signed by a session key are accepted when it is certified by the key of
+    /// `from_address` at the message timestamp.
+    Follow,
+}
+
+impl MessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn payload<T: Into<Vec<u8>>>(mut self, payload: T) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn from_address<T: Into<Vec<u8>>>(mut self, from_address: T) -> Self {
+        self.from_address = Some(from_address.into());
+        self
+    }
+
+    pub fn to_address<T: Into<Vec<u8>>>(mut self, to_address: T) -> Self {
+        self.to_address = Some(to_address.into());
+        self
+    }
+
+    /// Marks the payload as encrypted by the caller, `iv` must be set as well.
+    pub fn encrypted(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    pub fn iv<T: Into<Vec<u8>>>(mut self, iv: T) -> Self {
+        self.iv = Some(iv.into());
+        self
+    }
+
+    /// Encrypts the payload to `peer` with `iv` when building, `encrypted` and `iv` are
+    /// filled in. `iv` must be freshly generated from a secure RNG for every message.
+    pub fn encrypt_to(mut self, peer: PublicKey, iv: [u8; 16]) -> Self {
+        self.encrypt_to = Some((peer, iv));
+        self
+    }
+
+    pub fn w3b(mut self, w3b: W3bstreamOptions) -> Self {
+        self.w3b = Some(w3b);
+        self
+    }
+
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    pub fn last_edge_addr<T: Into<Vec<u8>>>(mut self, last_edge_addr: T) -> Self {
+        self.last_edge_addr = Some(last_edge_addr.into());
+        self
+    }
+
+    /// Signs with BIP-340 Schnorr instead of recoverable ECDSA, the x-only public key is
+    /// attached to the message. `aux_rand` should be freshly generated from a secure RNG.
+    pub fn schnorr(mut self, aux_rand: [u8; 32]) -> Self {
+        self.schnorr_aux_rand = Some(aux_rand);
+        self
+    }
+
+    /// Marks the message as signed by a session key certified with `cert`, the signer
+    /// passed to `build` must hold the session key.
+    pub fn delegation(mut self, cert: DelegationCertificate) -> Self {
+        self.delegation = Some(cert);
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (name, addr) in [
+            ("from_address", &self.from_address),
+            ("to_address", &self.to_address),
+            ("last_edge_addr", &self.last_edge_addr),
+        ] {
+            if let Some(addr) = addr {
+                ensure!(
+                    addr.len() == 20,
+                    "Bad {} length: expected=20 actual={}",
+                    name,
+                    addr.len()
+                );
+            }
+        }
+        if let Some(iv) = &self.iv {
+            ensure!(
+                iv.len() == 16,
+                "Bad IV length: expected=16 actual={}",
+                iv.len()
+            );
+            ensure!(
+                self.encrypted,
+                "IV is set but the payload is not encrypted!"
+            );
+        }
+        if self.encrypt_to.is_some() {
+            ensure!(
+                !self.encrypted && self.iv.is_none(),
+                "encrypt_to can not be used with a payload encrypted by the caller!"
+            );
+        } else {
+            ensure!(
+                !self.encrypted || self.iv.is_some(),
+                "IV is required when the payload is encrypted!"
+            );
+        }
+        Ok(())
+    }
+
+    /// Encodes the message in a COSE_Sign1 envelope signed with ES256K instead, see
+    /// `cose`. Schnorr signatures, delegations, W3bstream options and `last_edge_addr`
+    /// are not supported.
+    pub fn build_cose<S: Signer>(self, signer: &S) -> Result<Vec<u8>> {
+        ensure!(
+            self.schnorr_aux_rand.is_none()
+                && self.delegation.is_none()
+                && self.w3b.is_none()
+                && self.last_edge_addr.is_none(),
+            "COSE_Sign1 messages only support ES256K signatures by the key of from_address!"
+        );
+        let (raw, nonce) = self.build_raw(signer)?;
+        sign_cose_message(signer, &raw, nonce)
+    }
+
+    /// `RawMessage` with the payload encoded and encrypted, and the nonce.
+    fn build_raw<S: Signer>(&self, signer: &S) -> Result<(RawMessage, u64)> {
+        self.validate()?;
+
+        let timestamp = self
+            .timestamp
+            .ok_or(anyhow!("Message timestamp is required!"))?;
+        let nonce = self.nonce.ok_or(anyhow!("Message nonce is required!"))?;
+        let root_address = if let Some(cert) = &self.delegation {
+            let (root_address, session) = check_delegation(cert, timestamp)?;
+            ensure!(
+                session == signer.verifying_key(),
+                "The signer does not hold the delegated session key!"
+            );
+            Some(root_address)
+        } else {
+            None
+        };
+        let payload = if let Some(w3b) = &self.w3b {
+            encode_w3b_payload(self.payload.clone(), w3b.encoding())?
+        } else {
+            self.payload.clone()
+        };
+        let (payload, encrypted, iv) = if let Some((peer, iv)) = &self.encrypt_to {
+            let payload = encrypt_payload(signer, peer, payload.as_slice(), iv)?;
+            (payload, true, Some(iv.to_vec()))
+        } else {
+            (payload, self.encrypted, self.iv.clone())
+        };
+        let sender = root_address.unwrap_or_else(|| signer.address_bytes());
+        if let Some(from_address) = &self.from_address {
+            ensure!(
+                from_address.as_slice() == sender.as_slice(),
+                "from_address is not the address of the signer: from_address=0x{} signer=0x{}",
+                hex::encode(from_address),
+                hex::encode(sender)
+            );
+        }
+        let raw = RawMessage {
+            timestamp,
+            from_address: sender.to_vec(),
+            to_address: if let Some(t) = &self.to_address {
+                t.clone()
+            } else if let Some((peer, _)) = &self.encrypt_to {
+                get_eth_address_bytes(&(*peer).into()).into()
+            } else {
+                [0u8; 20].into()
+            },
+            encrypted,
+            payload,
+            iv,
+            w3b: self.w3b.clone(),
+        };
+        Ok((raw, nonce))
+    }
+
+    pub fn build<S: Signer>(self, signer: &S) -> Result<SignedMessage> {
+        let (raw, nonce) = self.build_raw(signer)?;
+        let raw = raw.encode_to_vec();
+        let mut hasher = Keccak256::new();
+        hasher.update(&raw);
+        hasher.update(nonce.to_string().as_bytes());
+        let raw_hash = hasher.finalize_reset();
+        hasher.update(raw_hash);
+        let (signature, signature_scheme, public_key) =
+            if let Some(aux_rand) = &self.schnorr_aux_rand {
+                let signature = signer.sign_schnorr(&hasher.finalize().into(), aux_rand)?;
+                (
+                    signature.to_bytes().to_vec(),
+                    Some(SignatureScheme::SsBip340Schnorr.into()),
+                    Some(signer.schnorr_verifying_key().to_bytes().to_vec()),
+                )
+            } else {
+                let (signature, recid) = signer.sign_digest_recoverable(hasher)?;
+                let mut sign_bytes = signature.to_vec();
+                sign_bytes.append(&mut vec![recid.to_byte()]);
+                (sign_bytes, None, None)
+            };
+
+        Ok(SignedMessage {
+            raw,
+            hash: raw_hash.to_vec(),
+            nonce,
+            signature,
+            last_edge_addr: self.last_edge_addr,
+            signature_scheme,
+            public_key,
+            delegation: self.delegation,
+        })
+    }
+}
+
+pub fn check_message(data: &[u8]) -> Result<(SignedMessage, RawMessage)> {
+    let (msg, raw_msg, _) = check_message_with_sender(data, DelegationMode::Reject)?;
+    Ok((msg, raw_msg))
+}
+
+/// Verifies the message, following the delegation to the root key of `from_address` if
+/// it is signed by a session key.
+pub fn check_delegated_message(data: &[u8]) -> Result<(SignedMessage, RawMessage)> {
+    let (msg, raw_msg, _) = check_message_with_sender(data, DelegationMode::Follow)?;
+    Ok((msg, raw_msg))
+}
+
+/// Verifies the message and returns its payload, decrypted if it was encrypted to `signer`.
+pub fn check_and_decrypt_message<S: Signer>(
+    data: &[u8],
+    signer: &S,
+) -> Result<(SignedMessage, RawMessage, Vec<u8>)> {
+    let (msg, raw_msg, sender) = check_message_with_sender(data, DelegationMode::Reject)?;
+    let payload = decrypt_checked_payload(&raw_msg, &sender, signer)?;
+    Ok((msg, raw_msg, payload))
+}
+
+pub(crate) fn decrypt_checked_payload<S: Signer>(
+    raw_msg: &RawMessage,
+    sender: &VerifyingKey,
+    signer: &S,
+) -> Result<Vec<u8>> {
+    if !raw_msg.encrypted {
+        return Ok(raw_msg.payload.clone());
+    }
+    ensure!(
+        raw_msg.to_address.as_slice() == signer.address_bytes().as_slice(),
+        "Encrypted message is not sent to me: to_address=0x{}",
+        hex::encode(raw_msg.to_address.as_slice())
+    );
+    let iv = raw_msg
+        .iv
+        .as_ref()
+        .ok_or(anyhow!("Encrypted message without IV!"))?;
+    decrypt_payload(
+        signer,
+        &(*sender).into(),
+        raw_msg.payload.as_slice(),
+        iv.as_slice(),
+    )
+}
+
+/// Verifies the message and returns the key that signed it, which is the session key for
+/// delegated messages.
+pub(crate) fn check_message_with_sender(
+    data: &[u8],
+    delegation: DelegationMode,
+) -> Result<(SignedMessage, RawMessage, VerifyingKey)> {
+    ensure!(!data.is_empty(), "Message should not be empty!");
+
+    let mut hasher = Keccak256::new();
+
+    let msg = SignedMessage::decode(data).map_err(|e| anyhow!("Bad SignedMessage: {}", e))?;
+    let SignedMessage {
+        raw,
+        hash,
+        nonce,
+        signature,
+        ..
+    } = msg.clone();
+    let raw = raw.as_slice();
+    let hash = hash.as_slice();
+    let hash_hex = hex::encode(hash);
+    hasher.update(raw);
+    hasher.update(nonce.to_string().as_bytes());
+    let curr_hash = hasher.finalize_reset();
+    ensure!(
+        hash == curr_hash.as_slice(),
+        "Hash verification failed: expected=0x{} current=0x{}",
+        hash_hex,
+        hex::encode(curr_hash)
+    );
+    debug!("Raw message hash: 0x{}", hash_hex);
+
+    let raw_msg = RawMessage::decode(raw).map_err(|e| anyhow!("Bad RawMessage: {}", e))?;
+    let RawMessage { from_address, .. } = raw_msg.clone();
+
+    let from_address = from_address.as_slice();
+    let from_address_hex = hex::encode(from_address);
+    let session = match (&msg.delegation, delegation) {
+        (None, _) => None,
+        (Some(_), DelegationMode::Reject) => {
+            bail!("Message is signed by a delegated session key!")
+        }
+        (Some(cert), DelegationMode::Follow) => {
+            let (root_address, session) = check_delegation(cert, raw_msg.timestamp)?;
+            ensure!(
+                from_address == root_address.as_slice(),
+                "Delegation check failed! expected_root=0x{} actual_root=0x{}",
+                from_address_hex,
+                hex::encode(root_address)
+            );
+            Some(get_eth_address_bytes(&session))
+        }
+    };
+    // Address of the key that signed the message.
+    let signer_address = session
+        .as_ref()
+        .map(|a| a.as_slice())
+        .unwrap_or(from_address);
+    let signer_address_hex = hex::encode(signer_address);
+    hasher.update(hash);
+    let scheme = match msg.signature_scheme {
+        Some(scheme) => SignatureScheme::try_from(scheme)
+            .map_err(|_| anyhow!("Unknown signature scheme: {}", scheme))?,
+        None => SignatureScheme::SsEcdsaRecoverable,
+    };
+    let r_key = match scheme {
+        SignatureScheme::SsEcdsaRecoverable => recover_ecdsa_signer(hasher, &signature)?,
+        SignatureScheme::SsBip340Schnorr => verify_schnorr_signer(
+            hasher,
+            &signature,
+            msg.public_key.as_deref().unwrap_or_default(),
+            signer_address,
+        )?,
+    };
+    let r_key_addr = get_eth_address_bytes(&r_key);
+    let r_key_addr = r_key_addr.as_ref();
+    ensure!(
+        signer_address == r_key_addr,
+        "Signature check failed! expected_signer=0x{} actual_signer=0x{}",
+        signer_address_hex,
+        hex::encode(r_key_addr)
+    );
+    debug!(
+        "Signer public key: 0x{}",
+        hex::encode(r_key.to_sec1_bytes())
+    );
+    debug!(
+        "Last touched: 0x{}",
+        if let Some(addr) = &msg.last_edge_addr {
+            let addr = addr.as_slice();
+            hex::encode(addr)
+        } else {
+            "None".to_string()
+        }
+    );
+
+    Ok((msg, raw_msg, r_key))
+}
+
+fn recover_ecdsa_signer(digest: Keccak256, signature: &[u8]) -> Result<VerifyingKey> {
+    ensure!(signature.len() == 65, "Bad signature length!");
+    let r = &signature[0..32];
+    let s = &signature[32..64];
+    let v = &signature[64..];
+    debug!(
+        "R: 0x{}\nS: 0x{}\nV: 0x{}",
+        hex::encode(r),
+        hex::encode(s),
+        hex::encode(v),
+    );
+    let rs = Signature::try_from(&signature[0..64]).map_err(|e| anyhow!("Bad signature: {}", e))?;
+    let v = RecoveryId::try_from(v[0]).map_err(|e| anyhow!("Bad recovery id: {}", e))?;
+    VerifyingKey::recover_from_digest(digest, &rs, v)
+        .map_err(|e| anyhow!("Failed to recover signer: {}", e))
+}
+
+/// Verifies a BIP-340 signature with the attached x-only public key, and returns the full
+/// public key of which the parity matches `from_address`.
+fn verify_schnorr_signer(
+    digest: Keccak256,
+    signature: &[u8],
+    public_key: &[u8],
+    from_address: &[u8],
+) -> Result<VerifyingKey> {
+    ensure!(signature.len() == 64, "Bad signature length!");
+    ensure!(public_key.len() == 32, "Bad x-only public key length!");
+    debug!(
+        "Schnorr signature: 0x{}\nx-only public key: 0x{}",
+        hex::encode(signature),
+        hex::encode(public_key)
+    );
+    let signature =
+        schnorr::Signature::try_from(signature).map_err(|e| anyhow!("Bad signature: {}", e))?;
+    let x_only = schnorr::VerifyingKey::from_bytes(public_key)
+        .map_err(|e| anyhow!("Bad x-only public key: {}", e))?;
+    x_only
+        .verify_prehash(&digest.finalize(), &signature)
+        .map_err(|e| anyhow!("Schnorr signature check failed: {}", e))?;
+
+    // Both keys with the x coordinate control the x-only key, the address tells which one
+    // the sender is using.
+    let mut sec1 = [0u8; 33];
+    sec1[1..].copy_from_slice(public_key);
+    let mut key = None;
+    for tag in [0x02, 0x03] {
+        sec1[0] = tag;
+        let k = VerifyingKey::from_sec1_bytes(&sec1)
+            .map_err(|e| anyhow!("Bad x-only public key: {}", e))?;
+        if get_eth_address_bytes(&k).as_slice() == from_address {
+            key = Some(k);
+        }
+    }
+    key.ok_or(anyhow!(
+        "Signature check failed! expected_signer=0x{} x_only_public_key=0x{}",
+        hex::encode(from_address),
+        hex::encode(public_key)
+    ))
+}
@@ -0,0 +1,211 @@
+use crate::preludes::*;
+use crate::signer::Signer;
+use k256::schnorr;
+use k256::schnorr::signature::hazmat::PrehashVerifier;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Signed Nostr event, see NIP-01.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NostrEvent {
+    pub id: [u8; 32],
+    /// x-only public key of the author.
+    pub pubkey: [u8; 32],
+    pub created_at: u64,
+    pub kind: u16,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: [u8; 64],
+}
+
+/// Builder of `NostrEvent`, `created_at` is required.
+#[derive(Clone, Default)]
+pub struct NostrEventBuilder {
+    created_at: Option<u64>,
+    kind: u16,
+    tags: Vec<Vec<String>>,
+    content: String,
+}
+
+/// Serialization of the event that the id is hashed from.
+fn serialize_for_id(
+    pubkey: &[u8; 32],
+    created_at: u64,
+    kind: u16,
+    tags: &[Vec<String>],
+    content: &str,
+) -> String {
+    json!([0, hex::encode(pubkey), created_at, kind, tags, content]).to_string()
+}
+
+fn event_id(
+    pubkey: &[u8; 32],
+    created_at: u64,
+    kind: u16,
+    tags: &[Vec<String>],
+    content: &str,
+) -> [u8; 32] {
+    Sha256::digest(serialize_for_id(pubkey, created_at, kind, tags, content).as_bytes()).into()
+}
+
+impl NostrEventBuilder {
+    pub fn new(kind: u16) -> Self {
+        Self {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    pub fn created_at(mut self, created_at: u64) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub fn tag<T: Into<String>, I: IntoIterator<Item = T>>(mut self, tag: I) -> Self {
+        self.tags.push(tag.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn content<T: Into<String>>(mut self, content: T) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    /// Signs the event with the BIP-340 key of `signer`, `aux_rand` should be freshly
+    /// generated from a secure RNG.
+    pub fn build<S: Signer>(self, signer: &S, aux_rand: &[u8; 32]) -> Result<NostrEvent> {
+        let created_at = self
+            .created_at
+            .ok_or(anyhow!("Event created_at is required!"))?;
+        let pubkey: [u8; 32] = signer.schnorr_verifying_key().to_bytes().into();
+        let id = event_id(&pubkey, created_at, self.kind, &self.tags, &self.content);
+        let sig = signer.sign_schnorr(&id, aux_rand)?;
+        Ok(NostrEvent {
+            id,
+            pubkey,
+            created_at,
+            kind: self.kind,
+            tags: self.tags,
+            content: self.content,
+            sig: sig.to_bytes(),
+        })
+    }
+}
+
+impl NostrEvent {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": hex::encode(self.id),
+            "pubkey": hex::encode(self.pubkey),
+            "created_at": self.created_at,
+            "kind": self.kind,
+            "tags": self.tags,
+            "content": self.content,
+            "sig": hex::encode(self.sig),
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value> {
+            value.get(name).ok_or(anyhow!("Event without {}!", name))
+        }
+        fn hex_field<const N: usize>(value: &Value, name: &str) -> Result<[u8; N]> {
+            let s = field(value, name)?
+                .as_str()
+                .ok_or(anyhow!("Bad event {}!", name))?;
+            let mut ret = [0u8; N];
+            hex::decode_to_slice(s, &mut ret).map_err(|e| anyhow!("Bad event {}: {}", name, e))?;
+            Ok(ret)
+        }
+
+        let tags = field(value, "tags")?
+            .as_array()
+            .ok_or(anyhow!("Bad event tags!"))?
+            .iter()
+            .map(|tag| {
+                tag.as_array()
+                    .ok_or(anyhow!("Bad event tag!"))?
+                    .iter()
+                    .map(|i| {
+                        i.as_str()
+                            .map(ToString::to_string)
+                            .ok_or(anyhow!("Bad event tag!"))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let kind = field(value, "kind")?
+            .as_u64()
+            .and_then(|k| u16::try_from(k).ok())
+            .ok_or(anyhow!("Bad event kind!"))?;
+        Ok(Self {
+            id: hex_field(value, "id")?,
+            pubkey: hex_field(value, "pubkey")?,
+            created_at: field(value, "created_at")?
+                .as_u64()
+                .ok_or(anyhow!("Bad event created_at!"))?,
+            kind,
+            tags,
+            content: field(value, "content")?
+                .as_str()
+                .ok_or(anyhow!("Bad event content!"))?
+                .to_string(),
+            sig: hex_field(value, "sig")?,
+        })
+    }
+
+    /// Checks the id and the signature of the event.
+    pub fn verify(&self) -> Result<()> {
+        let id = event_id(
+            &self.pubkey,
+            self.created_at,
+            self.kind,
+            &self.tags,
+            &self.content,
+        );
+        ensure!(
+            id == self.id,
+            "Event id verification failed: expected=0x{} current=0x{}",
+            hex::encode(self.id),
+            hex::encode(id)
+        );
+        let pubkey = schnorr::VerifyingKey::from_bytes(&self.pubkey)
+            .map_err(|e| anyhow!("Bad event pubkey: {}", e))?;
+        let sig = schnorr::Signature::try_from(self.sig.as_slice())
+            .map_err(|e| anyhow!("Bad event signature: {}", e))?;
+        pubkey
+            .verify_prehash(&self.id, &sig)
+            .map_err(|e| anyhow!("Event signature check failed: {}", e))
+    }
+
+    /// `["EVENT", <event>]` message to publish the event to a relay.
+    pub fn to_relay_message(&self) -> String {
+        json!(["EVENT", self.to_json()]).to_string()
+    }
+}
+
+/// Parses a relay message and returns the result of `["OK", <event_id>, <accepted>,
+/// <message>]` if it is one.
+pub fn parse_relay_ok(msg: &str) -> Result<Option<([u8; 32], bool, String)>> {
+    let msg: Value = serde_json::from_str(msg).map_err(|e| anyhow!("Bad relay message: {}", e))?;
+    let msg = msg.as_array().ok_or(anyhow!("Bad relay message!"))?;
+    if msg.first().and_then(Value::as_str) != Some("OK") {
+        return Ok(None);
+    }
+    let mut id = [0u8; 32];
+    hex::decode_to_slice(
+        msg.get(1).and_then(Value::as_str).unwrap_or_default(),
+        &mut id,
+    )
+    .map_err(|e| anyhow!("Bad event id in OK message: {}", e))?;
+    let accepted = msg
+        .get(2)
+        .and_then(Value::as_bool)
+        .ok_or(anyhow!("Bad OK message!"))?;
+    let message = msg
+        .get(3)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    Ok(Some((id, accepted, message)))
+}
@@ -0,0 +1,8 @@
+pub use crate::proto::*;
+pub use alloc::format;
+pub use alloc::string::{String, ToString};
+pub use alloc::vec;
+pub use alloc::vec::Vec;
+pub use anyhow::{anyhow, bail, ensure, Result};
+pub use log::*;
+pub use prost::Message;
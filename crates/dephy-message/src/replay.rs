@@ -0,0 +1,125 @@
+use crate::message::{check_message_with_sender, decrypt_checked_payload, DelegationMode};
+use crate::preludes::*;
+use crate::signer::Signer;
+use alloc::collections::BTreeMap;
+use k256::ecdsa::VerifyingKey;
+
+/// Storage of the last nonce accepted from every sender.
+pub trait ReplayGuard {
+    fn last_nonce(&self, from_address: &[u8]) -> Option<u64>;
+
+    fn accept(&mut self, from_address: &[u8], nonce: u64) -> Result<()>;
+}
+
+/// `ReplayGuard` keeping the nonces in memory, they are lost on restart.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReplayGuard {
+    last_nonces: BTreeMap<Vec<u8>, u64>,
+}
+
+impl MemoryReplayGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.last_nonces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last_nonces.is_empty()
+    }
+}
+
+impl ReplayGuard for MemoryReplayGuard {
+    fn last_nonce(&self, from_address: &[u8]) -> Option<u64> {
+        self.last_nonces.get(from_address).copied()
+    }
+
+    fn accept(&mut self, from_address: &[u8], nonce: u64) -> Result<()> {
+        self.last_nonces.insert(from_address.to_vec(), nonce);
+        Ok(())
+    }
+}
+
+/// Verifies messages like `check_message`, and additionally rejects:
+/// - messages with a nonce not greater than the last one accepted from the same sender;
+/// - messages with a timestamp more than `max_clock_skew` seconds away from local time.
+///
+/// Nonces of delegated messages are tracked under the root address in `from_address`.
+pub struct MessageChecker<G: ReplayGuard> {
+    guard: G,
+    max_clock_skew: u64,
+    delegation: DelegationMode,
+}
+
+impl<G: ReplayGuard> MessageChecker<G> {
+    pub fn new(guard: G, max_clock_skew: u64) -> Self {
+        Self {
+            guard,
+            max_clock_skew,
+            delegation: DelegationMode::Reject,
+        }
+    }
+
+    pub fn delegation(mut self, delegation: DelegationMode) -> Self {
+        self.delegation = delegation;
+        self
+    }
+
+    pub fn guard(&self) -> &G {
+        &self.guard
+    }
+
+    pub fn guard_mut(&mut self) -> &mut G {
+        &mut self.guard
+    }
+
+    /// `now` is the local time in seconds since the Unix epoch.
+    pub fn check(&mut self, data: &[u8], now: u64) -> Result<(SignedMessage, RawMessage)> {
+        let (msg, raw_msg, _) = self.check_with_sender(data, now)?;
+        Ok((msg, raw_msg))
+    }
+
+    /// Same as `check`, and returns the payload decrypted if it was encrypted to `signer`.
+    pub fn check_and_decrypt<S: Signer>(
+        &mut self,
+        data: &[u8],
+        now: u64,
+        signer: &S,
+    ) -> Result<(SignedMessage, RawMessage, Vec<u8>)> {
+        let (msg, raw_msg, sender) = self.check_with_sender(data, now)?;
+        let payload = decrypt_checked_payload(&raw_msg, &sender, signer)?;
+        Ok((msg, raw_msg, payload))
+    }
+
+    fn check_with_sender(
+        &mut self,
+        data: &[u8],
+        now: u64,
+    ) -> Result<(SignedMessage, RawMessage, VerifyingKey)> {
+        let (msg, raw_msg, sender) = check_message_with_sender(data, self.delegation)?;
+        let from_address = raw_msg.from_address.as_slice();
+
+        let skew = raw_msg.timestamp.abs_diff(now);
+        ensure!(
+            skew <= self.max_clock_skew,
+            "Message timestamp out of range: timestamp={} now={} max_clock_skew={}",
+            raw_msg.timestamp,
+            now,
+            self.max_clock_skew
+        );
+        if let Some(last) = self.guard.last_nonce(from_address) {
+            ensure!(
+                msg.nonce > last,
+                "Nonce replayed: from=0x{} nonce={} last_nonce={}",
+                hex::encode(from_address),
+                msg.nonce,
+                last
+            );
+        }
+        self.guard.accept(from_address, msg.nonce)?;
+
+        Ok((msg, raw_msg, sender))
+    }
+}
@@ -0,0 +1,46 @@
+//! Minimal RLP encoder, enough for Ethereum transactions.
+
+use crate::preludes::*;
+
+fn encode_length(len: usize, offset: u8, out: &mut Vec<u8>) {
+    if len < 56 {
+        out.push(offset + len as u8);
+    } else {
+        let len = (len as u64).to_be_bytes();
+        let len = strip_leading_zeros(&len);
+        out.push(offset + 55 + len.len() as u8);
+        out.extend_from_slice(len);
+    }
+}
+
+pub(crate) fn strip_leading_zeros(buf: &[u8]) -> &[u8] {
+    let start = buf.iter().position(|b| *b != 0).unwrap_or(buf.len());
+    &buf[start..]
+}
+
+/// Encodes a byte string.
+pub fn rlp_encode_bytes(buf: &[u8]) -> Vec<u8> {
+    if buf.len() == 1 && buf[0] < 0x80 {
+        return buf.to_vec();
+    }
+    let mut ret = Vec::with_capacity(buf.len() + 9);
+    encode_length(buf.len(), 0x80, &mut ret);
+    ret.extend_from_slice(buf);
+    ret
+}
+
+/// Encodes an unsigned integer as a big-endian byte string without leading zeros.
+pub fn rlp_encode_uint(value: u128) -> Vec<u8> {
+    rlp_encode_bytes(strip_leading_zeros(&value.to_be_bytes()))
+}
+
+/// Encodes a list of items that are already RLP encoded.
+pub fn rlp_encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let len = items.iter().map(|i| i.len()).sum();
+    let mut ret = Vec::with_capacity(len + 9);
+    encode_length(len, 0xc0, &mut ret);
+    for item in items {
+        ret.extend_from_slice(item);
+    }
+    ret
+}
@@ -0,0 +1,75 @@
+use crate::crypto::{get_eth_address_bytes, get_shared_key};
+use crate::preludes::*;
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use k256::{schnorr, PublicKey, SecretKey};
+use sha3::{Digest, Keccak256};
+
+/// Holder of a secp256k1 identity that messages are signed with.
+pub trait Signer {
+    fn verifying_key(&self) -> VerifyingKey;
+
+    fn address_bytes(&self) -> [u8; 20] {
+        get_eth_address_bytes(&self.verifying_key())
+    }
+
+    /// Recoverable ECDSA signature of the 32-byte `prehash`, for schemes hashing with
+    /// something else than Keccak-256.
+    fn sign_prehash_recoverable(&self, prehash: &[u8; 32]) -> Result<(Signature, RecoveryId)>;
+
+    fn sign_digest_recoverable(&self, digest: Keccak256) -> Result<(Signature, RecoveryId)> {
+        self.sign_prehash_recoverable(&digest.finalize().into())
+    }
+
+    /// x-only public key for BIP-340 signatures, derived from the same key.
+    fn schnorr_verifying_key(&self) -> schnorr::VerifyingKey {
+        let point = self.verifying_key().to_encoded_point(true);
+        schnorr::VerifyingKey::from_bytes(point.x().unwrap()).unwrap()
+    }
+
+    /// BIP-340 signature of the 32-byte `prehash`, `aux_rand` should be freshly generated
+    /// for every signature.
+    fn sign_schnorr(&self, prehash: &[u8; 32], aux_rand: &[u8; 32]) -> Result<schnorr::Signature>;
+
+    /// AES-256 key negotiated with `peer`, see `crypto::get_shared_key`.
+    fn shared_key(&self, peer: &PublicKey) -> Result<[u8; 32]>;
+}
+
+/// Signer holding its key in memory, for keys that are not stored in eFuse and for testing.
+#[derive(Clone)]
+pub struct SoftwareSigner {
+    key: SecretKey,
+}
+
+impl SoftwareSigner {
+    pub fn new(key: SecretKey) -> Self {
+        Self { key }
+    }
+
+    pub fn from_slice(buf: &[u8]) -> Result<Self> {
+        Ok(Self::new(
+            SecretKey::from_slice(buf).map_err(|_| anyhow!("Invalid secret key!"))?,
+        ))
+    }
+}
+
+impl Signer for SoftwareSigner {
+    fn verifying_key(&self) -> VerifyingKey {
+        self.key.public_key().into()
+    }
+
+    fn sign_prehash_recoverable(&self, prehash: &[u8; 32]) -> Result<(Signature, RecoveryId)> {
+        let key: SigningKey = self.key.clone().into();
+        key.sign_prehash_recoverable(prehash)
+            .map_err(|e| anyhow!("Failed to sign: {}", e))
+    }
+
+    fn sign_schnorr(&self, prehash: &[u8; 32], aux_rand: &[u8; 32]) -> Result<schnorr::Signature> {
+        let key: schnorr::SigningKey = (&self.key).into();
+        key.sign_prehash_with_aux_rand(prehash, aux_rand)
+            .map_err(|e| anyhow!("Failed to sign: {}", e))
+    }
+
+    fn shared_key(&self, peer: &PublicKey) -> Result<[u8; 32]> {
+        Ok(get_shared_key(&self.key, peer))
+    }
+}
@@ -0,0 +1,178 @@
+use crate::crypto::to_checksum_address;
+use crate::eth::personal_sign;
+use crate::preludes::*;
+use crate::signer::Signer;
+use core::fmt;
+use serde_json::{json, Value};
+
+/// Sign-In with Ethereum message, see EIP-4361.
+///
+/// Timestamps are RFC 3339 strings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SiweMessage {
+    /// Authority requesting the signing, like `example.com` or `example.com:8443`.
+    pub domain: String,
+    pub address: [u8; 20],
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: String,
+    pub expiration_time: Option<String>,
+    pub not_before: Option<String>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+impl SiweMessage {
+    pub fn new<D: Into<String>, U: Into<String>, N: Into<String>, T: Into<String>>(
+        domain: D,
+        address: [u8; 20],
+        uri: U,
+        chain_id: u64,
+        nonce: N,
+        issued_at: T,
+    ) -> Self {
+        Self {
+            domain: domain.into(),
+            address,
+            statement: None,
+            uri: uri.into(),
+            version: "1".to_string(),
+            chain_id,
+            nonce: nonce.into(),
+            issued_at: issued_at.into(),
+            expiration_time: None,
+            not_before: None,
+            request_id: None,
+            resources: vec![],
+        }
+    }
+
+    pub fn statement<T: Into<String>>(mut self, statement: T) -> Self {
+        self.statement = Some(statement.into());
+        self
+    }
+
+    pub fn expiration_time<T: Into<String>>(mut self, timestamp: T) -> Self {
+        self.expiration_time = Some(timestamp.into());
+        self
+    }
+
+    pub fn not_before<T: Into<String>>(mut self, timestamp: T) -> Self {
+        self.not_before = Some(timestamp.into());
+        self
+    }
+
+    pub fn request_id<T: Into<String>>(mut self, request_id: T) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn resource<T: Into<String>>(mut self, uri: T) -> Self {
+        self.resources.push(uri.into());
+        self
+    }
+
+    /// Checks the fields that servers would reject the message for.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(!self.domain.is_empty(), "SIWE domain is required!");
+        ensure!(!self.uri.is_empty(), "SIWE URI is required!");
+        ensure!(
+            self.nonce.len() >= 8 && self.nonce.chars().all(|c| c.is_ascii_alphanumeric()),
+            "Bad SIWE nonce, expected at least 8 alphanumeric characters: {:?}",
+            self.nonce
+        );
+        if let Some(statement) = &self.statement {
+            ensure!(
+                !statement.contains('\n'),
+                "SIWE statement must be a single line!"
+            );
+        }
+        Ok(())
+    }
+
+    /// EIP-191 signature of the message, as sent to the server along with the message.
+    pub fn sign<S: Signer>(&self, signer: &S) -> Result<[u8; 65]> {
+        ensure!(
+            self.address == signer.address_bytes(),
+            "SIWE address is not the one of the signer!"
+        );
+        self.validate()?;
+        personal_sign(signer, self.to_string().as_bytes())
+    }
+
+    /// JSON body of the login request, `{"message": ..., "signature": "0x..."}`.
+    pub fn login_request<S: Signer>(&self, signer: &S) -> Result<String> {
+        let signature = self.sign(signer)?;
+        Ok(json!({
+            "message": self.to_string(),
+            "signature": format!("0x{}", hex::encode(signature)),
+        })
+        .to_string())
+    }
+}
+
+impl fmt::Display for SiweMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} wants you to sign in with your Ethereum account:",
+            self.domain
+        )?;
+        writeln!(f, "{}", to_checksum_address(&self.address))?;
+        writeln!(f)?;
+        if let Some(statement) = &self.statement {
+            writeln!(f, "{}", statement)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "URI: {}", self.uri)?;
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Chain ID: {}", self.chain_id)?;
+        writeln!(f, "Nonce: {}", self.nonce)?;
+        write!(f, "Issued At: {}", self.issued_at)?;
+        if let Some(t) = &self.expiration_time {
+            write!(f, "\nExpiration Time: {}", t)?;
+        }
+        if let Some(t) = &self.not_before {
+            write!(f, "\nNot Before: {}", t)?;
+        }
+        if let Some(id) = &self.request_id {
+            write!(f, "\nRequest ID: {}", id)?;
+        }
+        if !self.resources.is_empty() {
+            write!(f, "\nResources:")?;
+            for uri in self.resources.iter() {
+                write!(f, "\n- {}", uri)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Nonce in the response of a SIWE nonce endpoint, either plain text or JSON with a
+/// `nonce` field.
+pub fn parse_siwe_nonce(body: &str) -> Result<String> {
+    let body = body.trim();
+    let nonce = match serde_json::from_str::<Value>(body) {
+        Ok(Value::Object(v)) => v
+            .get("nonce")
+            .and_then(Value::as_str)
+            .ok_or(anyhow!("No nonce in response: {}", body))?
+            .to_string(),
+        Ok(Value::String(nonce)) => nonce,
+        _ => body.to_string(),
+    };
+    Ok(nonce)
+}
+
+/// Session token in the JSON response of a SIWE login endpoint, from the `token` or
+/// `access_token` field.
+pub fn parse_siwe_token(body: &str) -> Option<String> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    ["token", "access_token"]
+        .into_iter()
+        .find_map(|k| body.get(k).and_then(Value::as_str))
+        .map(ToString::to_string)
+}
@@ -0,0 +1,137 @@
+use crate::preludes::*;
+use crate::rlp::{rlp_encode_bytes, rlp_encode_list, rlp_encode_uint, strip_leading_zeros};
+use crate::signer::Signer;
+use sha3::{Digest, Keccak256};
+
+/// Entry of an EIP-2930 access list.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: [u8; 20],
+    pub storage_keys: Vec<[u8; 32]>,
+}
+
+/// Legacy transaction, replay-protected with EIP-155 when `chain_id` is set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LegacyTransaction {
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    /// `None` to create a contract.
+    pub to: Option<[u8; 20]>,
+    pub value: u128,
+    pub data: Vec<u8>,
+}
+
+/// EIP-1559 (type 2) transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    /// `None` to create a contract.
+    pub to: Option<[u8; 20]>,
+    pub value: u128,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+}
+
+fn encode_to(to: &Option<[u8; 20]>) -> Vec<u8> {
+    match to {
+        Some(to) => rlp_encode_bytes(to),
+        None => rlp_encode_bytes(&[]),
+    }
+}
+
+fn encode_signature_word(word: &[u8]) -> Vec<u8> {
+    rlp_encode_bytes(strip_leading_zeros(word))
+}
+
+impl LegacyTransaction {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_encode_uint(self.nonce as u128),
+            rlp_encode_uint(self.gas_price),
+            rlp_encode_uint(self.gas_limit as u128),
+            encode_to(&self.to),
+            rlp_encode_uint(self.value),
+            rlp_encode_bytes(&self.data),
+        ]
+    }
+
+    /// Hash to be signed, see EIP-155.
+    pub fn signing_hash(&self) -> Keccak256 {
+        let mut fields = self.fields();
+        if let Some(chain_id) = self.chain_id {
+            fields.push(rlp_encode_uint(chain_id as u128));
+            fields.push(rlp_encode_uint(0));
+            fields.push(rlp_encode_uint(0));
+        }
+        Keccak256::new_with_prefix(rlp_encode_list(&fields))
+    }
+
+    /// Signs the transaction and returns the raw transaction for `eth_sendRawTransaction`.
+    pub fn sign<S: Signer>(&self, signer: &S) -> Result<Vec<u8>> {
+        let (signature, recid) = signer.sign_digest_recoverable(self.signing_hash())?;
+        let v = match self.chain_id {
+            Some(chain_id) => chain_id as u128 * 2 + 35 + recid.is_y_odd() as u128,
+            None => 27 + recid.is_y_odd() as u128,
+        };
+        let (r, s) = signature.split_bytes();
+        let mut fields = self.fields();
+        fields.push(rlp_encode_uint(v));
+        fields.push(encode_signature_word(&r));
+        fields.push(encode_signature_word(&s));
+        Ok(rlp_encode_list(&fields))
+    }
+}
+
+impl Eip1559Transaction {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        let access_list = self
+            .access_list
+            .iter()
+            .map(|i| {
+                let keys = i
+                    .storage_keys
+                    .iter()
+                    .map(|k| rlp_encode_bytes(k))
+                    .collect::<Vec<_>>();
+                rlp_encode_list(&[rlp_encode_bytes(&i.address), rlp_encode_list(&keys)])
+            })
+            .collect::<Vec<_>>();
+        vec![
+            rlp_encode_uint(self.chain_id as u128),
+            rlp_encode_uint(self.nonce as u128),
+            rlp_encode_uint(self.max_priority_fee_per_gas),
+            rlp_encode_uint(self.max_fee_per_gas),
+            rlp_encode_uint(self.gas_limit as u128),
+            encode_to(&self.to),
+            rlp_encode_uint(self.value),
+            rlp_encode_bytes(&self.data),
+            rlp_encode_list(&access_list),
+        ]
+    }
+
+    /// Hash to be signed, `keccak256(0x02 || rlp([chain_id, ..., access_list]))`.
+    pub fn signing_hash(&self) -> Keccak256 {
+        let mut hasher = Keccak256::new_with_prefix([2u8]);
+        hasher.update(rlp_encode_list(&self.fields()));
+        hasher
+    }
+
+    /// Signs the transaction and returns the raw transaction for `eth_sendRawTransaction`.
+    pub fn sign<S: Signer>(&self, signer: &S) -> Result<Vec<u8>> {
+        let (signature, recid) = signer.sign_digest_recoverable(self.signing_hash())?;
+        let (r, s) = signature.split_bytes();
+        let mut fields = self.fields();
+        fields.push(rlp_encode_uint(recid.is_y_odd() as u128));
+        fields.push(encode_signature_word(&r));
+        fields.push(encode_signature_word(&s));
+        let mut ret = vec![2u8];
+        ret.append(&mut rlp_encode_list(&fields));
+        Ok(ret)
+    }
+}
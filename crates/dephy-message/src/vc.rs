@@ -0,0 +1,123 @@
+use crate::crypto::did_str_to_addr_bytes;
+use crate::did::addr_bytes_to_did_str;
+use crate::jwt::{sign_jwt_es256k, Jwt};
+use crate::preludes::*;
+use crate::signer::Signer;
+use serde_json::{json, Map, Value};
+
+pub static VC_CONTEXT_V1: &str = "https://www.w3.org/2018/credentials/v1";
+pub static VC_TYPE: &str = "VerifiableCredential";
+
+/// Builder of W3C Verifiable Credentials issued by a `did:dephy` identity, encoded as JWT
+/// with ES256K (JWT-VC, see "JSON Web Token" in VC Data Model 1.1).
+///
+/// `issued_at` is required.
+#[derive(Clone, Default)]
+pub struct CredentialBuilder {
+    id: Option<String>,
+    types: Vec<String>,
+    subject_id: Option<String>,
+    subject: Map<String, Value>,
+    issued_at: Option<u64>,
+    expires_at: Option<u64>,
+}
+
+impl CredentialBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Id of the credential, as the `jti` claim.
+    pub fn id<T: Into<String>>(mut self, id: T) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Type of the credential besides `VerifiableCredential`.
+    pub fn credential_type<T: Into<String>>(mut self, credential_type: T) -> Self {
+        self.types.push(credential_type.into());
+        self
+    }
+
+    /// Id of the subject, as the `sub` claim.
+    pub fn subject_id<T: Into<String>>(mut self, id: T) -> Self {
+        self.subject_id = Some(id.into());
+        self
+    }
+
+    /// A claim about the subject in `credentialSubject`.
+    pub fn claim<T: Into<String>, V: Into<Value>>(mut self, name: T, value: V) -> Self {
+        self.subject.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn issued_at(mut self, timestamp: u64) -> Self {
+        self.issued_at = Some(timestamp);
+        self
+    }
+
+    pub fn expires_at(mut self, timestamp: u64) -> Self {
+        self.expires_at = Some(timestamp);
+        self
+    }
+
+    /// Signs the credential with the key of `signer`, which is also the issuer.
+    pub fn issue<S: Signer>(self, signer: &S) -> Result<String> {
+        let issued_at = self
+            .issued_at
+            .ok_or(anyhow!("Credential issued_at is required!"))?;
+        let issuer = addr_bytes_to_did_str(&signer.address_bytes());
+
+        let mut types = vec![VC_TYPE.to_string()];
+        types.extend(self.types);
+        let mut claims = json!({
+            "iss": issuer,
+            "nbf": issued_at,
+            "vc": {
+                "@context": [VC_CONTEXT_V1],
+                "type": types,
+                "credentialSubject": self.subject,
+            },
+        });
+        if let Some(id) = self.id {
+            claims["jti"] = id.into();
+        }
+        if let Some(id) = self.subject_id {
+            claims["sub"] = id.into();
+        }
+        if let Some(exp) = self.expires_at {
+            claims["exp"] = exp.into();
+        }
+
+        let header = json!({
+            "typ": "JWT",
+            "kid": format!("{}#key-1", issuer),
+        });
+        sign_jwt_es256k(signer, header, &claims)
+    }
+}
+
+/// Verifies a JWT-VC issued by a `did:dephy` identity, and that it is valid at `now`.
+///
+/// Returns the address of the issuer and the claims of the JWT.
+pub fn check_credential(token: &str, now: u64) -> Result<([u8; 20], Value)> {
+    let jwt = Jwt::decode(token)?;
+    let issuer = jwt
+        .claims
+        .get("iss")
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("Credential without issuer!"))?;
+    let issuer: [u8; 20] = did_str_to_addr_bytes(issuer)?
+        .try_into()
+        .map_err(|_| anyhow!("Bad issuer DID!"))?;
+    jwt.verify_es256k_address(&issuer)?;
+    jwt.check_time(now, 0)?;
+    ensure!(
+        jwt.claims
+            .pointer("/vc/type")
+            .and_then(Value::as_array)
+            .is_some_and(|t| t.iter().any(|t| t == VC_TYPE)),
+        "Not a verifiable credential!"
+    );
+    Ok((issuer, jwt.claims))
+}
@@ -0,0 +1,51 @@
+use dephy_message::*;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+const AUDIENCE: &str = "https://send.testnet.dephy.io";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+#[test]
+fn auth_token_round_trip() {
+    let token = sign_auth_token(&signer(), AUDIENCE, 1700000000, 60, &[7u8; 16]).unwrap();
+    let auth = check_auth_token(&token, AUDIENCE, 1700000030, 0).unwrap();
+    assert_eq!(hex::encode(auth.issuer), ADDR_HEX);
+    assert_eq!(auth.audience, AUDIENCE);
+    assert_eq!(auth.issued_at, 1700000000);
+    assert_eq!(auth.expires_at, 1700000060);
+    assert_eq!(auth.nonce, "07".repeat(16));
+
+    let claims = Jwt::decode(&token).unwrap().claims;
+    assert_eq!(claims["sub"], format!("did:dephy:0x{}", ADDR_HEX));
+
+    assert!(check_auth_token(&token, "https://other.example.com", 1700000030, 0).is_err());
+    assert!(check_auth_token(&token, AUDIENCE, 1700000060, 0).is_err());
+    check_auth_token(&token, AUDIENCE, 1700000060, 5).unwrap();
+
+    // Signed by another key than the one of the issuer DID.
+    let other = SoftwareSigner::from_slice(&[1u8; 32]).unwrap();
+    let forged = sign_jwt_es256k(&other, serde_json::json!({}), &claims).unwrap();
+    assert!(check_auth_token(&forged, AUDIENCE, 1700000030, 0).is_err());
+}
+
+#[test]
+fn bearer_header_and_audience() {
+    assert_eq!(parse_bearer("Bearer abc.def.ghi"), Some("abc.def.ghi"));
+    assert_eq!(parse_bearer(" bearer  abc.def.ghi "), Some("abc.def.ghi"));
+    assert_eq!(parse_bearer("Basic dXNlcjpwYXNz"), None);
+    assert_eq!(parse_bearer("Bearer"), None);
+
+    assert_eq!(
+        url_audience("https://send.testnet.dephy.io/dephy/signed_message").unwrap(),
+        AUDIENCE
+    );
+    assert_eq!(
+        url_audience("HTTP://user@Example.com:3883?x=1").unwrap(),
+        "http://example.com:3883"
+    );
+    assert!(url_audience("example.com/path").is_err());
+    assert!(url_audience("https:///path").is_err());
+}
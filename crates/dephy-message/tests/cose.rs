@@ -0,0 +1,155 @@
+use dephy_message::*;
+use prost::Message;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+const PEER_KEY_HEX: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+// Parsed and verified with the `coset` crate.
+const COSE_HEX: &str = "d28444a101382ea104542c7536e3605d9c16a7a3d7b1898e529396a65c23583fa6011a\
+    6553f10002542c7536e3605d9c16a7a3d7b1898e529396a65c2303540000000000000000000000000000000000\
+    00000004f4054568656c6c6f08182a5840b9fe01962f824df3218d8bdd3cc62f340e60b2c9a17e6c54373292c4\
+    1d749f1a04854101302054818472c9213fafe80876f153336dbed2d627b31bd482612a03";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn peer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(PEER_KEY_HEX).unwrap()).unwrap()
+}
+
+fn builder() -> MessageBuilder {
+    MessageBuilder::new()
+        .timestamp(1700000000)
+        .nonce(42)
+        .payload("hello")
+}
+
+#[test]
+fn cbor_encoding() {
+    // Examples of RFC 8949, Appendix A.
+    for (value, expected) in [
+        (CborValue::int(0), "00"),
+        (CborValue::int(23), "17"),
+        (CborValue::int(24), "1818"),
+        (CborValue::int(1000), "1903e8"),
+        (CborValue::int(1000000), "1a000f4240"),
+        (CborValue::Unsigned(u64::MAX), "1bffffffffffffffff"),
+        (CborValue::int(-1), "20"),
+        (CborValue::int(-1000), "3903e7"),
+        (CborValue::Negative(u64::MAX), "3bffffffffffffffff"),
+        (CborValue::Bytes(vec![1, 2, 3, 4]), "4401020304"),
+        (CborValue::Text("IETF".to_string()), "6449455446"),
+        (CborValue::Bool(false), "f4"),
+        (CborValue::Null, "f6"),
+        (
+            CborValue::Array(vec![
+                CborValue::int(1),
+                CborValue::Array(vec![CborValue::int(2), CborValue::int(3)]),
+            ]),
+            "8201820203",
+        ),
+        (
+            CborValue::Map(vec![
+                (CborValue::int(1), CborValue::int(2)),
+                (CborValue::int(3), CborValue::int(4)),
+            ]),
+            "a201020304",
+        ),
+        (
+            CborValue::Tag(1, Box::new(CborValue::int(1363896240))),
+            "c11a514b67b0",
+        ),
+    ] {
+        assert_eq!(hex::encode(value.encode()), expected);
+        assert_eq!(
+            CborValue::decode(&hex::decode(expected).unwrap()).unwrap(),
+            value
+        );
+    }
+    assert_eq!(CborValue::int(-1000).as_i64(), Some(-1000));
+
+    // Indefinite lengths, truncated and trailing data, and deep nesting.
+    assert!(CborValue::decode(&hex::decode("9f01ff").unwrap()).is_err());
+    assert!(CborValue::decode(&hex::decode("4401020304ff").unwrap()).is_err());
+    assert!(CborValue::decode(&hex::decode("5bffffffffffffffff").unwrap()).is_err());
+    assert!(CborValue::decode(&hex::decode("9a7fffffff").unwrap()).is_err());
+    assert!(CborValue::decode(&[0x81; 64]).is_err());
+}
+
+#[test]
+fn cose_sign1_known_answer() {
+    let data = builder().build_cose(&signer()).unwrap();
+    assert_eq!(hex::encode(&data), COSE_HEX);
+    assert!(is_cose_sign1(&data));
+    assert!(!is_cose_sign1(
+        &builder().build(&signer()).unwrap().encode_to_vec()
+    ));
+
+    let (raw, nonce) = check_cose_message(&data).unwrap();
+    assert_eq!(nonce, 42);
+    assert_eq!(raw.timestamp, 1700000000);
+    assert_eq!(hex::encode(&raw.from_address), ADDR_HEX);
+    assert_eq!(raw.to_address, vec![0u8; 20]);
+    assert!(!raw.encrypted);
+    assert_eq!(raw.payload, b"hello");
+}
+
+#[test]
+fn cose_sign1_rejects_tampering() {
+    let data = hex::decode(COSE_HEX).unwrap();
+    // The payload "hello" is at the end of the map, before the nonce.
+    let pos = data.windows(5).position(|w| w == b"hello").unwrap();
+    let mut tampered = data.clone();
+    tampered[pos] = b'j';
+    assert!(check_cose_message(&tampered).is_err());
+
+    // Claiming another sender.
+    let other = SoftwareSigner::from_slice(&[2u8; 32]).unwrap();
+    assert!(builder()
+        .from_address(signer().address_bytes())
+        .build_cose(&other)
+        .is_err());
+
+    // An algorithm other than ES256K in the protected header.
+    let mut other_alg = data.clone();
+    assert_eq!(other_alg[5], 0x38);
+    other_alg[6] = 0x2f;
+    assert!(check_cose_message(&other_alg).is_err());
+
+    // Untagged messages are accepted, other tags are not.
+    check_cose_message(&data[1..]).unwrap();
+    let mut other_tag = data.clone();
+    other_tag[0] = 0xd1;
+    assert!(check_cose_message(&other_tag).is_err());
+    assert!(check_cose_message(&data[..data.len() - 1]).is_err());
+}
+
+#[test]
+fn cose_sign1_encrypted_round_trip() {
+    let data = builder()
+        .payload("secret reading")
+        .encrypt_to(peer().verifying_key().into(), [7u8; 16])
+        .build_cose(&signer())
+        .unwrap();
+    let (raw, _) = check_cose_message(&data).unwrap();
+    assert!(raw.encrypted);
+    assert_eq!(raw.iv, Some(vec![7u8; 16]));
+    assert_eq!(raw.to_address, peer().address_bytes().to_vec());
+
+    let (_, _, payload) = check_and_decrypt_cose_message(&data, &peer()).unwrap();
+    assert_eq!(payload, b"secret reading");
+    assert!(check_and_decrypt_cose_message(&data, &signer()).is_err());
+
+    assert!(builder().schnorr([0u8; 32]).build_cose(&signer()).is_err());
+    assert!(builder()
+        .w3b(W3bstreamOptions {
+            topic: "t".to_string(),
+            token: "t".to_string(),
+            encoding: W3bstreamPayloadEncoding::WpeUtf8.into(),
+        })
+        .build_cose(&signer())
+        .is_err());
+}
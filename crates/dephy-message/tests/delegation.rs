@@ -0,0 +1,97 @@
+use dephy_message::*;
+use prost::Message;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+const SESSION_KEY_HEX: &str = "0202020202020202020202020202020202020202020202020202020202020202";
+
+fn root() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn session() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(SESSION_KEY_HEX).unwrap()).unwrap()
+}
+
+fn delegated_builder(timestamp: u64) -> MessageBuilder {
+    let cert = create_delegation(&root(), &session().verifying_key(), 1000, 2000).unwrap();
+    MessageBuilder::new()
+        .timestamp(timestamp)
+        .nonce(1)
+        .payload("hello")
+        .delegation(cert)
+}
+
+#[test]
+fn delegation_certificate() {
+    let cert = create_delegation(&root(), &session().verifying_key(), 1000, 2000).unwrap();
+    let (root_address, session_key) = check_delegation(&cert, 1000).unwrap();
+    assert_eq!(hex::encode(root_address), ADDR_HEX);
+    assert_eq!(session_key, session().verifying_key());
+    assert!(check_delegation(&cert, 2000).is_ok());
+    assert!(check_delegation(&cert, 999).is_err());
+    assert!(check_delegation(&cert, 2001).is_err());
+    assert!(create_delegation(&root(), &session().verifying_key(), 2, 1).is_err());
+
+    let signer = recover_eth_signer(delegation_digest(&cert.raw), &cert.signature).unwrap();
+    assert_eq!(hex::encode(get_eth_address_bytes(&signer)), ADDR_HEX);
+
+    // Data the device signs with `personal_sign` on request must not pass as a certificate
+    // of the device key.
+    let mut signed = cert.clone();
+    signed.signature = personal_sign(&root(), &cert.raw).unwrap().to_vec();
+    let root_address = check_delegation(&signed, 1000).map(|(addr, _)| hex::encode(addr));
+    assert_ne!(root_address.ok().as_deref(), Some(ADDR_HEX));
+    let mut msg = delegated_builder(1500).build(&session()).unwrap();
+    msg.delegation = Some(signed);
+    assert!(check_delegated_message(&msg.encode_to_vec()).is_err());
+}
+
+#[test]
+fn delegated_message() {
+    let msg = delegated_builder(1500).build(&session()).unwrap();
+    let data = msg.encode_to_vec();
+
+    let (_, raw) = check_delegated_message(&data).unwrap();
+    assert_eq!(hex::encode(&raw.from_address), ADDR_HEX);
+    assert!(check_message(&data).is_err());
+
+    let schnorr = delegated_builder(1500)
+        .schnorr([1u8; 32])
+        .build(&session())
+        .unwrap();
+    assert!(check_delegated_message(&schnorr.encode_to_vec()).is_ok());
+
+    // Signed outside the validity window.
+    assert!(delegated_builder(2001).build(&session()).is_err());
+
+    // Signed by a key other than the certified session key.
+    assert!(delegated_builder(1500).build(&root()).is_err());
+    let mut forged = delegated_builder(1500)
+        .from_address(hex::decode(ADDR_HEX).unwrap())
+        .build(&session())
+        .unwrap();
+    forged.delegation =
+        Some(create_delegation(&session(), &session().verifying_key(), 1000, 2000).unwrap());
+    assert!(check_delegated_message(&forged.encode_to_vec()).is_err());
+}
+
+#[test]
+fn message_checker_follows_delegation() {
+    let data = delegated_builder(1500)
+        .build(&session())
+        .unwrap()
+        .encode_to_vec();
+
+    let mut checker = MessageChecker::new(MemoryReplayGuard::new(), 60);
+    assert!(checker.check(&data, 1500).is_err());
+
+    let mut checker =
+        MessageChecker::new(MemoryReplayGuard::new(), 60).delegation(DelegationMode::Follow);
+    assert!(checker.check(&data, 1500).is_ok());
+    assert!(checker.check(&data, 1500).is_err());
+    assert_eq!(
+        checker.guard().last_nonce(&hex::decode(ADDR_HEX).unwrap()),
+        Some(1)
+    );
+}
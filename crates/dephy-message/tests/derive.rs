@@ -0,0 +1,52 @@
+use dephy_message::*;
+use k256::SecretKey;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn root() -> SecretKey {
+    SecretKey::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+#[test]
+fn derived_keys_match_hkdf_sha256() {
+    assert_eq!(
+        hex::encode(derive_key(&root(), KeyPurpose::Signing).unwrap().to_bytes()),
+        "8d98287ed3d3e14d346c8b27794cd7527e16514ae00ec9224269facc019b3774"
+    );
+    assert_eq!(
+        hex::encode(
+            derive_key(&root(), KeyPurpose::Encryption)
+                .unwrap()
+                .to_bytes()
+        ),
+        "811c8de986a3c40a8e778e080985c8b516bed60bf532ce53e94856c779f13b93"
+    );
+}
+
+#[test]
+fn derived_keys_are_independent() {
+    let purposes = [
+        KeyPurpose::Signing,
+        KeyPurpose::Encryption,
+        KeyPurpose::BlePairing,
+        KeyPurpose::TransportAuth,
+    ];
+    let keys = purposes
+        .iter()
+        .map(|p| derive_key(&root(), *p).unwrap().to_bytes())
+        .collect::<Vec<_>>();
+    for (i, key) in keys.iter().enumerate() {
+        assert_ne!(*key, root().to_bytes());
+        assert_eq!(keys.iter().filter(|k| *k == key).count(), 1);
+        assert_eq!(
+            KeyPurpose::from_label(purposes[i].label()),
+            Some(purposes[i])
+        );
+    }
+    assert_eq!(
+        derive_key_with_label(&root(), "dephy:signing")
+            .unwrap()
+            .to_bytes(),
+        keys[0]
+    );
+}
@@ -0,0 +1,62 @@
+use dephy_message::*;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+#[test]
+fn did_string_round_trip() {
+    let did = addr_bytes_to_did_str(&signer().address_bytes());
+    assert_eq!(did, format!("did:dephy:0x{}", ADDR_HEX));
+    assert_eq!(
+        did_str_to_addr_bytes(did).unwrap(),
+        signer().address_bytes()
+    );
+}
+
+#[test]
+fn did_document_of_key() {
+    let services = parse_did_services(
+        "dephy|DePHYMessageEndpoint|https://send.testnet.dephy.io/dephy/signed_message; \
+         nostr|NostrRelay|wss://relay.example.com;",
+    )
+    .unwrap();
+    assert_eq!(services.len(), 2);
+    assert_eq!(
+        services[1],
+        DidService::new("nostr", "NostrRelay", "wss://relay.example.com")
+    );
+    assert!(parse_did_services("dephy|DePHYMessageEndpoint").is_err());
+    assert!(parse_did_services("").unwrap().is_empty());
+
+    let doc = did_document(&signer().verifying_key(), &services);
+    let did = format!("did:dephy:0x{}", ADDR_HEX);
+    assert_eq!(doc["id"], did);
+    let method = &doc["verificationMethod"][0];
+    assert_eq!(method["id"], format!("{}#key-1", did));
+    assert_eq!(method["type"], "Multikey");
+    assert_eq!(method["controller"], did);
+    assert_eq!(
+        method["publicKeyMultibase"],
+        public_key_multibase(&signer().verifying_key())
+    );
+    assert_eq!(doc["authentication"][0], "#key-1");
+    assert_eq!(doc["service"][0]["id"], format!("{}#dephy", did));
+    assert_eq!(doc["service"][1]["type"], "NostrRelay");
+    assert_eq!(
+        doc["service"][1]["serviceEndpoint"],
+        "wss://relay.example.com"
+    );
+}
+
+#[test]
+fn multikey_encoding() {
+    let multibase = public_key_multibase(&signer().verifying_key());
+    assert!(multibase.starts_with("zQ3s"));
+    let buf = bs58::decode(&multibase[1..]).into_vec().unwrap();
+    assert_eq!(buf[..2], [0xe7, 0x01]);
+    assert_eq!(buf[2..], *signer().verifying_key().to_sec1_bytes());
+}
@@ -0,0 +1,89 @@
+use dephy_message::*;
+use sha3::{Digest, Keccak256};
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+fn address(s: &str) -> [u8; 20] {
+    hex::decode(s).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn personal_sign_matches_web3() {
+    // Example of `web3.eth.accounts.sign` from the web3.js documentation.
+    let signer = SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap();
+    assert_eq!(
+        hex::encode(eip191_digest(b"Some data").finalize()),
+        "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655"
+    );
+    let signature = personal_sign(&signer, b"Some data").unwrap();
+    assert_eq!(
+        hex::encode(signature),
+        "b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd\
+         6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c"
+    );
+    let key = recover_eth_signer(eip191_digest(b"Some data"), &signature).unwrap();
+    assert_eq!(hex::encode(get_eth_address_bytes(&key)), ADDR_HEX);
+}
+
+#[test]
+fn typed_data_matches_eip712_example() {
+    let person = |name: &str, wallet: &str| {
+        Eip712Struct::new("Person")
+            .member("name", "string", Eip712Value::String(name.into()))
+            .member("wallet", "address", Eip712Value::address(address(wallet)))
+    };
+    let mail = Eip712Struct::new("Mail")
+        .member(
+            "from",
+            "Person",
+            Eip712Value::Struct(person("Cow", "cd2a3d9f938e13cd947ec05abc7fe734df8dd826")),
+        )
+        .member(
+            "to",
+            "Person",
+            Eip712Value::Struct(person("Bob", "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb")),
+        )
+        .member(
+            "contents",
+            "string",
+            Eip712Value::String("Hello, Bob!".into()),
+        );
+    let domain = Eip712Domain {
+        name: Some("Ether Mail".into()),
+        version: Some("1".into()),
+        chain_id: Some(1),
+        verifying_contract: Some(address("cccccccccccccccccccccccccccccccccccccccc")),
+        salt: None,
+    };
+
+    assert_eq!(
+        mail.encode_type(),
+        "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+    );
+    assert_eq!(
+        hex::encode(domain.separator()),
+        "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+    );
+    assert_eq!(
+        hex::encode(mail.hash()),
+        "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+    );
+    assert_eq!(
+        hex::encode(eip712_digest(&domain, &mail).finalize()),
+        "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+    );
+
+    let key = Keccak256::digest(b"cow");
+    let signer = SoftwareSigner::from_slice(&key).unwrap();
+    assert_eq!(
+        hex::encode(signer.address_bytes()),
+        "cd2a3d9f938e13cd947ec05abc7fe734df8dd826"
+    );
+    let signature = sign_typed_data(&signer, &domain, &mail).unwrap();
+    assert_eq!(
+        hex::encode(signature),
+        "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+         07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c"
+    );
+}
@@ -0,0 +1,95 @@
+use dephy_message::*;
+use serde_json::json;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+// Verified with the `cryptography` Python package.
+const TOKEN: &str = "eyJhbGciOiJFUzI1NksiLCJ0eXAiOiJKV1QifQ.\
+    eyJpYXQiOjE3MDAwMDAwMDAsInN1YiI6ImRlcGh5In0.\
+    FrWH80EsFG3oYZH8rDgWQih0MOPNwuliw4KW6CtpQ-tZ7U_Rqv7Bx6_Rx1HDMYU3NTJ53WrqUhNXm4JMckBuIg";
+
+// Same claims signed by the `cryptography` Python package, with a high S.
+const TOKEN_HIGH_S: &str = "eyJhbGciOiJFUzI1NksiLCJ0eXAiOiJKV1QifQ.\
+    eyJpYXQiOjE3MDAwMDAwMDAsInN1YiI6ImRlcGh5In0.\
+    faaIES4-BQEBMON6dP726n9mvzP8KbnlEIM-pbODZ7TivzBOMPOG5aI1lHgK72NH2xyXFwX7muxoeAwgIOIzHA";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn address() -> [u8; 20] {
+    hex::decode(ADDR_HEX).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn es256k_known_answer() {
+    let token = sign_jwt_es256k(
+        &signer(),
+        json!({ "typ": "JWT" }),
+        &json!({ "sub": "dephy", "iat": 1700000000u64 }),
+    )
+    .unwrap();
+    assert_eq!(token, TOKEN);
+
+    let jwt = Jwt::decode(&token).unwrap();
+    assert_eq!(jwt.header, json!({ "alg": "ES256K", "typ": "JWT" }));
+    assert_eq!(jwt.claims["sub"], "dephy");
+    jwt.verify_es256k(&signer().verifying_key()).unwrap();
+    assert_eq!(
+        jwt.verify_es256k_address(&address()).unwrap(),
+        signer().verifying_key()
+    );
+    assert!(jwt.verify_es256k_address(&[0u8; 20]).is_err());
+
+    let jwt = Jwt::decode(TOKEN_HIGH_S).unwrap();
+    jwt.verify_es256k(&signer().verifying_key()).unwrap();
+    jwt.verify_es256k_address(&address()).unwrap();
+}
+
+#[test]
+fn es256k_rejects_tampered_token() {
+    let (input, signature) = TOKEN.rsplit_once('.').unwrap();
+    let (header, _) = input.split_once('.').unwrap();
+    let claims = base64::Engine::encode(
+        &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+        json!({ "sub": "mallory", "iat": 1700000000u64 }).to_string(),
+    );
+    let tampered = format!("{}.{}.{}", header, claims, signature);
+    let jwt = Jwt::decode(&tampered).unwrap();
+    assert!(jwt.verify_es256k(&signer().verifying_key()).is_err());
+    assert!(jwt.verify_es256k_address(&address()).is_err());
+
+    let jwt = Jwt::decode(&TOKEN.replace(
+        "eyJhbGciOiJFUzI1NksiLCJ0eXAiOiJKV1QifQ",
+        "eyJhbGciOiJub25lIn0",
+    ))
+    .unwrap();
+    assert!(jwt.verify_es256k(&signer().verifying_key()).is_err());
+
+    assert!(Jwt::decode("a.b").is_err());
+    assert!(Jwt::decode(&format!("{}.x", TOKEN)).is_err());
+}
+
+#[test]
+fn time_claims() {
+    let token = sign_jwt_es256k(
+        &signer(),
+        serde_json::Value::Null,
+        &json!({ "nbf": 1000u64, "exp": 2000u64 }),
+    )
+    .unwrap();
+    let jwt = Jwt::decode(&token).unwrap();
+    assert!(jwt.check_time(999, 0).is_err());
+    jwt.check_time(999, 1).unwrap();
+    jwt.check_time(1000, 0).unwrap();
+    jwt.check_time(1999, 0).unwrap();
+    assert!(jwt.check_time(2000, 0).is_err());
+    jwt.check_time(2000, 1).unwrap();
+
+    let jwt =
+        Jwt::decode(&sign_jwt_es256k(&signer(), json!({}), &json!({ "exp": "soon" })).unwrap())
+            .unwrap();
+    assert!(jwt.check_time(0, 0).is_err());
+    assert!(sign_jwt_es256k(&signer(), json!([]), &json!({})).is_err());
+}
@@ -0,0 +1,254 @@
+use dephy_message::*;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use prost::Message;
+use sha3::{Digest, Keccak256};
+
+// Private key from the web3.js documentation, the address is well known.
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+const PEER_KEY_HEX: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn peer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(PEER_KEY_HEX).unwrap()).unwrap()
+}
+
+fn build_message() -> SignedMessage {
+    MessageBuilder::new()
+        .timestamp(1700000000)
+        .nonce(42)
+        .payload("hello")
+        .build(&signer())
+        .unwrap()
+}
+
+#[test]
+fn address_from_key() {
+    assert_eq!(hex::encode(signer().address_bytes()), ADDR_HEX);
+    assert_eq!(
+        get_eth_address(&signer().verifying_key()),
+        format!("0x{}", ADDR_HEX)
+    );
+}
+
+#[test]
+fn hash_is_keccak_of_raw_and_nonce_string() {
+    let msg = build_message();
+
+    assert_eq!(
+        hex::encode(&msg.raw),
+        "0880e2cfaa0612142c7536e3605d9c16a7a3d7b1898e529396a65c231a14\
+         000000000000000000000000000000000000000020002a0568656c6c6f"
+    );
+
+    let mut hasher = Keccak256::new();
+    hasher.update(&msg.raw);
+    hasher.update(b"42");
+    assert_eq!(msg.hash, hasher.finalize().to_vec());
+    assert_eq!(
+        hex::encode(&msg.hash),
+        "95f0bb3f07a2cecd2c4a30774ff77f68dda5170e20da0c1e2a22b00f91a8dac2"
+    );
+}
+
+#[test]
+fn signature_is_over_keccak_of_hash() {
+    let msg = build_message();
+    assert_eq!(msg.signature.len(), 65);
+
+    let rs = Signature::try_from(&msg.signature[0..64]).unwrap();
+    let v = RecoveryId::try_from(msg.signature[64]).unwrap();
+    let digest = Keccak256::new_with_prefix(&msg.hash);
+    let key = VerifyingKey::recover_from_digest(digest, &rs, v).unwrap();
+    assert_eq!(hex::encode(get_eth_address_bytes(&key)), ADDR_HEX);
+    // RFC 6979 signatures are deterministic, any change of the scheme shows up here.
+    assert_eq!(
+        hex::encode(&msg.signature),
+        "9c683784d5fceda6e6fe1e8eec6b76f0471c9d628260c1460e44d4239efcac52\
+         3b686c1e021454d05dd032c7986b16498688c970fa70a3518a83fea23e1ea0aa01"
+    );
+}
+
+#[test]
+fn check_message_round_trip() {
+    let msg = build_message();
+    let (checked, raw) = check_message(&msg.encode_to_vec()).unwrap();
+    assert_eq!(checked, msg);
+    assert_eq!(raw.timestamp, 1700000000);
+    assert_eq!(hex::encode(&raw.from_address), ADDR_HEX);
+    assert_eq!(raw.to_address, vec![0u8; 20]);
+    assert_eq!(raw.payload, b"hello");
+    assert!(!raw.encrypted);
+}
+
+#[test]
+fn check_message_rejects_tampering() {
+    let msg = build_message();
+
+    let mut bad_nonce = msg.clone();
+    bad_nonce.nonce = 43;
+    assert!(check_message(&bad_nonce.encode_to_vec()).is_err());
+
+    let mut bad_raw = msg.clone();
+    let mut raw = RawMessage::decode(bad_raw.raw.as_slice()).unwrap();
+    raw.payload = b"hellO".to_vec();
+    bad_raw.raw = raw.encode_to_vec();
+    assert!(check_message(&bad_raw.encode_to_vec()).is_err());
+
+    let mut bad_signature = msg;
+    bad_signature.signature[10] ^= 1;
+    assert!(check_message(&bad_signature.encode_to_vec()).is_err());
+}
+
+#[test]
+fn encrypted_payload_round_trip() {
+    let msg = MessageBuilder::new()
+        .timestamp(1700000000)
+        .nonce(43)
+        .payload("secret reading")
+        .encrypt_to(peer().verifying_key().into(), [7u8; 16])
+        .build(&signer())
+        .unwrap();
+    let data = msg.encode_to_vec();
+
+    let (_, raw) = check_message(&data).unwrap();
+    assert!(raw.encrypted);
+    assert_eq!(raw.iv, Some(vec![7u8; 16]));
+    assert_eq!(raw.to_address, peer().address_bytes().to_vec());
+    assert_ne!(raw.payload, b"secret reading");
+
+    let (_, _, payload) = check_and_decrypt_message(&data, &peer()).unwrap();
+    assert_eq!(payload, b"secret reading");
+    assert!(check_and_decrypt_message(&data, &signer()).is_err());
+}
+
+#[test]
+fn builder_validation() {
+    let builder = MessageBuilder::new().timestamp(1).nonce(1);
+    assert!(builder
+        .clone()
+        .to_address([0u8; 19])
+        .build(&signer())
+        .is_err());
+    assert!(builder
+        .clone()
+        .last_edge_addr([0u8; 21])
+        .build(&signer())
+        .is_err());
+    assert!(builder.clone().encrypted(true).build(&signer()).is_err());
+    assert!(builder
+        .clone()
+        .encrypted(true)
+        .iv([0u8; 15])
+        .build(&signer())
+        .is_err());
+    assert!(builder.clone().iv([0u8; 16]).build(&signer()).is_err());
+    assert!(builder
+        .clone()
+        .encrypted(true)
+        .iv([0u8; 16])
+        .build(&signer())
+        .is_ok());
+    assert!(builder
+        .clone()
+        .from_address(peer().address_bytes())
+        .build(&signer())
+        .is_err());
+    assert!(builder
+        .clone()
+        .from_address(signer().address_bytes())
+        .build(&signer())
+        .is_ok());
+    assert!(MessageBuilder::new().nonce(1).build(&signer()).is_err());
+    assert!(MessageBuilder::new().timestamp(1).build(&signer()).is_err());
+}
+
+#[test]
+fn w3b_payload_encoding() {
+    let payload = b"{\"t\":1}".to_vec();
+    assert_eq!(
+        encode_w3b_payload(payload.clone(), W3bstreamPayloadEncoding::WpeUtf8Json).unwrap(),
+        payload
+    );
+    assert_eq!(
+        encode_w3b_payload(payload.clone(), W3bstreamPayloadEncoding::WpeHex).unwrap(),
+        b"7b2274223a317d"
+    );
+    assert_eq!(
+        encode_w3b_payload(payload, W3bstreamPayloadEncoding::WpeBase64).unwrap(),
+        b"eyJ0IjoxfQ=="
+    );
+    assert!(encode_w3b_payload(b"t=1".to_vec(), W3bstreamPayloadEncoding::WpeUtf8Json).is_err());
+    assert!(encode_w3b_payload(vec![0xff], W3bstreamPayloadEncoding::WpeUtf8).is_err());
+}
+
+#[test]
+fn did_string() {
+    let addr = did_str_to_addr_bytes(format!("did:dephy:0x{}", ADDR_HEX)).unwrap();
+    assert_eq!(hex::encode(addr), ADDR_HEX);
+    assert!(did_str_to_addr_bytes(format!("did:ethr:0x{}", ADDR_HEX)).is_err());
+    assert!(did_str_to_addr_bytes("did:dephy:0x1234").is_err());
+}
+
+/// `msg` with `raw` signed by `signer` as `MessageBuilder` does.
+fn schnorr_resign(
+    mut msg: SignedMessage,
+    raw: RawMessage,
+    signer: &SoftwareSigner,
+) -> SignedMessage {
+    msg.raw = raw.encode_to_vec();
+    let mut hasher = Keccak256::new();
+    hasher.update(&msg.raw);
+    hasher.update(msg.nonce.to_string().as_bytes());
+    msg.hash = hasher.finalize_reset().to_vec();
+    hasher.update(&msg.hash);
+    msg.signature = signer
+        .sign_schnorr(&hasher.finalize().into(), &[9u8; 32])
+        .unwrap()
+        .to_bytes()
+        .to_vec();
+    msg
+}
+
+#[test]
+fn schnorr_signature_round_trip() {
+    let builder = MessageBuilder::new()
+        .timestamp(1700000000)
+        .nonce(44)
+        .payload("hello")
+        .schnorr([9u8; 32]);
+    let msg = builder.clone().build(&signer()).unwrap();
+    assert_eq!(msg.signature.len(), 64);
+    assert_eq!(msg.signature_scheme(), SignatureScheme::SsBip340Schnorr);
+    assert_eq!(
+        msg.public_key,
+        Some(signer().schnorr_verifying_key().to_bytes().to_vec())
+    );
+
+    let (_, raw) = check_message(&msg.encode_to_vec()).unwrap();
+    assert_eq!(hex::encode(&raw.from_address), ADDR_HEX);
+
+    let mut bad_signature = msg.clone();
+    bad_signature.signature[10] ^= 1;
+    assert!(check_message(&bad_signature.encode_to_vec()).is_err());
+
+    // Another key can't sign as the sender, neither with the builder nor by hand.
+    assert!(builder
+        .clone()
+        .from_address(signer().address_bytes())
+        .build(&peer())
+        .is_err());
+    let forged = builder.build(&peer()).unwrap();
+    let mut raw = RawMessage::decode(forged.raw.as_slice()).unwrap();
+    raw.from_address = signer().address_bytes().to_vec();
+    let forged = schnorr_resign(forged, raw, &peer());
+    assert!(check_message(&forged.encode_to_vec()).is_err());
+
+    let mut no_scheme = msg;
+    no_scheme.signature_scheme = None;
+    assert!(check_message(&no_scheme.encode_to_vec()).is_err());
+}
@@ -0,0 +1,62 @@
+use dephy_message::*;
+use sha2::{Digest, Sha256};
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn build_event() -> NostrEvent {
+    NostrEventBuilder::new(1)
+        .created_at(1700000000)
+        .tag(["t", "dephy"])
+        .content("DePHY_0102,23.5\n\"ok\"")
+        .build(&signer(), &[0u8; 32])
+        .unwrap()
+}
+
+#[test]
+fn event_id_is_sha256_of_serialization() {
+    let event = build_event();
+    let pubkey = hex::encode(signer().schnorr_verifying_key().to_bytes());
+    assert_eq!(event.pubkey.to_vec(), hex::decode(&pubkey).unwrap());
+    let serialized = format!(
+        "[0,\"{}\",1700000000,1,[[\"t\",\"dephy\"]],\"DePHY_0102,23.5\\n\\\"ok\\\"\"]",
+        pubkey
+    );
+    assert_eq!(event.id, <[u8; 32]>::from(Sha256::digest(serialized)));
+    event.verify().unwrap();
+}
+
+#[test]
+fn event_json_round_trip() {
+    let event = build_event();
+    let msg: serde_json::Value = serde_json::from_str(&event.to_relay_message()).unwrap();
+    assert_eq!(msg[0], "EVENT");
+    let parsed = NostrEvent::from_json(&msg[1]).unwrap();
+    assert_eq!(parsed, event);
+
+    let mut bad_content = parsed.clone();
+    bad_content.content.push('!');
+    assert!(bad_content.verify().is_err());
+
+    let mut bad_sig = parsed;
+    bad_sig.sig[0] ^= 1;
+    assert!(bad_sig.verify().is_err());
+}
+
+#[test]
+fn relay_ok_message() {
+    let id = hex::encode([7u8; 32]);
+    assert_eq!(
+        parse_relay_ok(&format!("[\"OK\",\"{}\",true,\"\"]", id)).unwrap(),
+        Some(([7u8; 32], true, String::new()))
+    );
+    assert_eq!(
+        parse_relay_ok(&format!("[\"OK\",\"{}\",false,\"blocked: spam\"]", id)).unwrap(),
+        Some(([7u8; 32], false, "blocked: spam".to_string()))
+    );
+    assert_eq!(parse_relay_ok("[\"NOTICE\",\"hello\"]").unwrap(), None);
+    assert!(parse_relay_ok("not json").is_err());
+}
@@ -0,0 +1,39 @@
+use dephy_message::*;
+use prost::Message;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn message(timestamp: u64, nonce: u64) -> Vec<u8> {
+    let signer = SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap();
+    MessageBuilder::new()
+        .timestamp(timestamp)
+        .nonce(nonce)
+        .payload("hello")
+        .build(&signer)
+        .unwrap()
+        .encode_to_vec()
+}
+
+#[test]
+fn rejects_duplicate_and_stale_nonces() {
+    let mut checker = MessageChecker::new(MemoryReplayGuard::new(), 60);
+    let now = 1700000000;
+
+    assert!(checker.check(&message(now, 10), now).is_ok());
+    assert!(checker.check(&message(now, 10), now).is_err());
+    assert!(checker.check(&message(now, 9), now).is_err());
+    assert!(checker.check(&message(now, 11), now).is_ok());
+    assert_eq!(checker.guard().len(), 1);
+}
+
+#[test]
+fn rejects_clock_skew() {
+    let mut checker = MessageChecker::new(MemoryReplayGuard::new(), 60);
+    let now = 1700000000;
+
+    assert!(checker.check(&message(now - 61, 1), now).is_err());
+    assert!(checker.check(&message(now + 61, 2), now).is_err());
+    // Rejected messages must not move the last nonce forward.
+    assert!(checker.guard().is_empty());
+    assert!(checker.check(&message(now + 60, 1), now).is_ok());
+}
@@ -0,0 +1,136 @@
+use dephy_message::*;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn addr(s: &str) -> [u8; 20] {
+    hex::decode(s.trim_start_matches("0x"))
+        .unwrap()
+        .try_into()
+        .unwrap()
+}
+
+#[test]
+fn eip55_checksum() {
+    // Test cases of EIP-55.
+    for s in [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ] {
+        assert_eq!(to_checksum_address(&addr(s)), s);
+    }
+}
+
+#[test]
+fn eip4361_message() {
+    // Example of EIP-4361.
+    let msg = SiweMessage::new(
+        "service.invalid",
+        addr("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+        "https://service.invalid/login",
+        1,
+        "32891756",
+        "2021-09-30T16:25:24Z",
+    )
+    .statement("I accept the ServiceOrg Terms of Service: https://service.invalid/tos")
+    .resource("ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/")
+    .resource("https://example.com/my-web2-claim.json");
+    assert_eq!(
+        msg.to_string(),
+        "service.invalid wants you to sign in with your Ethereum account:\n\
+         0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2\n\
+         \n\
+         I accept the ServiceOrg Terms of Service: https://service.invalid/tos\n\
+         \n\
+         URI: https://service.invalid/login\n\
+         Version: 1\n\
+         Chain ID: 1\n\
+         Nonce: 32891756\n\
+         Issued At: 2021-09-30T16:25:24Z\n\
+         Resources:\n\
+         - ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/\n\
+         - https://example.com/my-web2-claim.json"
+    );
+
+    let msg = SiweMessage::new(
+        "example.com:8443",
+        addr("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+        "https://example.com:8443",
+        5,
+        "abcdefgh",
+        "2021-09-30T16:25:24Z",
+    )
+    .expiration_time("2021-09-30T16:35:24Z")
+    .request_id("42");
+    assert_eq!(
+        msg.to_string(),
+        "example.com:8443 wants you to sign in with your Ethereum account:\n\
+         0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2\n\
+         \n\
+         \n\
+         URI: https://example.com:8443\n\
+         Version: 1\n\
+         Chain ID: 5\n\
+         Nonce: abcdefgh\n\
+         Issued At: 2021-09-30T16:25:24Z\n\
+         Expiration Time: 2021-09-30T16:35:24Z\n\
+         Request ID: 42"
+    );
+}
+
+#[test]
+fn sign_in_request() {
+    let msg = SiweMessage::new(
+        "dashboard.example.com",
+        signer().address_bytes(),
+        "https://dashboard.example.com/login",
+        1,
+        "k3Jd9aQ2",
+        "2023-11-14T22:13:20Z",
+    );
+    let body: serde_json::Value =
+        serde_json::from_str(&msg.login_request(&signer()).unwrap()).unwrap();
+    assert_eq!(body["message"], msg.to_string());
+    let signature =
+        hex::decode(body["signature"].as_str().unwrap().trim_start_matches("0x")).unwrap();
+    let key = recover_eth_signer(eip191_digest(msg.to_string().as_bytes()), &signature).unwrap();
+    assert_eq!(key, signer().verifying_key());
+
+    let other = SiweMessage {
+        address: [0u8; 20],
+        ..msg.clone()
+    };
+    assert!(other.sign(&signer()).is_err());
+    let short_nonce = SiweMessage {
+        nonce: "1234".to_string(),
+        ..msg
+    };
+    assert!(short_nonce.sign(&signer()).is_err());
+}
+
+#[test]
+fn login_responses() {
+    assert_eq!(parse_siwe_nonce("  k3Jd9aQ2\n").unwrap(), "k3Jd9aQ2");
+    assert_eq!(parse_siwe_nonce("\"k3Jd9aQ2\"").unwrap(), "k3Jd9aQ2");
+    assert_eq!(
+        parse_siwe_nonce("{\"nonce\":\"k3Jd9aQ2\"}").unwrap(),
+        "k3Jd9aQ2"
+    );
+    assert!(parse_siwe_nonce("{\"error\":\"nope\"}").is_err());
+
+    assert_eq!(
+        parse_siwe_token("{\"ok\":true,\"token\":\"abc\"}"),
+        Some("abc".to_string())
+    );
+    assert_eq!(
+        parse_siwe_token("{\"access_token\":\"abc\"}"),
+        Some("abc".to_string())
+    );
+    assert_eq!(parse_siwe_token("{\"ok\":true}"), None);
+    assert_eq!(parse_siwe_token("ok"), None);
+}
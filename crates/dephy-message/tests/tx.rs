@@ -0,0 +1,108 @@
+use dephy_message::*;
+
+// Key of the EIP-155 example.
+const KEY_HEX: &str = "4646464646464646464646464646464646464646464646464646464646464646";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+#[test]
+fn rlp_encoding() {
+    assert_eq!(rlp_encode_bytes(b""), [0x80]);
+    assert_eq!(rlp_encode_bytes(b"\x7f"), [0x7f]);
+    assert_eq!(rlp_encode_bytes(b"\x80"), [0x81, 0x80]);
+    assert_eq!(rlp_encode_bytes(b"dog"), b"\x83dog");
+    assert_eq!(rlp_encode_uint(0), [0x80]);
+    assert_eq!(rlp_encode_uint(1024), [0x82, 0x04, 0x00]);
+    assert_eq!(rlp_encode_list(&[]), [0xc0]);
+    assert_eq!(
+        rlp_encode_list(&[rlp_encode_bytes(b"cat"), rlp_encode_bytes(b"dog")]),
+        b"\xc8\x83cat\x83dog"
+    );
+    let long = [b'a'; 56];
+    let mut expected = vec![0xb8, 56];
+    expected.extend_from_slice(&long);
+    assert_eq!(rlp_encode_bytes(&long), expected);
+}
+
+#[test]
+fn legacy_transaction_matches_eip155_example() {
+    let tx = LegacyTransaction {
+        chain_id: Some(1),
+        nonce: 9,
+        gas_price: 20_000_000_000,
+        gas_limit: 21000,
+        to: Some([0x35; 20]),
+        value: 1_000_000_000_000_000_000,
+        data: vec![],
+    };
+    assert_eq!(
+        hex::encode(sha3::Digest::finalize(tx.signing_hash())),
+        "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+    );
+    assert_eq!(
+        hex::encode(tx.sign(&signer()).unwrap()),
+        "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025\
+         a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276\
+         a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+    );
+}
+
+#[test]
+fn eip1559_transaction() {
+    let tx = Eip1559Transaction {
+        chain_id: 1,
+        nonce: 9,
+        max_priority_fee_per_gas: 2_000_000_000,
+        max_fee_per_gas: 30_000_000_000,
+        gas_limit: 21000,
+        to: Some([0x35; 20]),
+        value: 1_000_000_000_000_000_000,
+        data: vec![],
+        access_list: vec![],
+    };
+    assert_eq!(
+        hex::encode(tx.sign(&signer()).unwrap()),
+        "02f873010984773594008506fc23ac00825208943535353535353535353535353535353535353535\
+         880de0b6b3a764000080c080\
+         a02b03b67e070f45175ce9d07c4512720168bd468a24edb6997977a53d48c87a12\
+         a0733d775fdd689d306e08ac8ab399f34b5a0253b47ed81b8bf2d2a6ea607fcac7"
+    );
+}
+
+#[test]
+fn eip1559_contract_creation_with_access_list() {
+    let mut key_3 = [0u8; 32];
+    key_3[31] = 3;
+    let mut key_7 = [0u8; 32];
+    key_7[31] = 7;
+    let tx = Eip1559Transaction {
+        chain_id: 4689,
+        nonce: 0,
+        max_priority_fee_per_gas: 0,
+        max_fee_per_gas: 1_000_000_000_000,
+        gas_limit: 100000,
+        to: None,
+        value: 0,
+        data: hex::decode("6080604052").unwrap(),
+        access_list: vec![AccessListItem {
+            address: hex::decode("de0b295669a9fd93d5f28d9ec85e40f4cb697bae")
+                .unwrap()
+                .try_into()
+                .unwrap(),
+            storage_keys: vec![key_3, key_7],
+        }],
+    };
+    // `r` has a leading zero byte, which must be stripped.
+    assert_eq!(
+        hex::encode(tx.sign(&signer()).unwrap()),
+        "02f8b6821251808085e8d4a51000830186a08080856080604052\
+         f85bf85994de0b295669a9fd93d5f28d9ec85e40f4cb697baef842\
+         a00000000000000000000000000000000000000000000000000000000000000003\
+         a00000000000000000000000000000000000000000000000000000000000000007\
+         80\
+         9f8fc2c2321f2838bcba549f01cb7cea4df0a4f21730041cb172ff83c2aa1041\
+         a010dfa2dc35732012d8d8bdce765260e3a9f028d141b483526c2fab5f24dd84a6"
+    );
+}
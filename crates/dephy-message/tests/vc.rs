@@ -0,0 +1,78 @@
+use dephy_message::*;
+use serde_json::json;
+
+const KEY_HEX: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDR_HEX: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+fn signer() -> SoftwareSigner {
+    SoftwareSigner::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap()
+}
+
+fn reading_credential() -> CredentialBuilder {
+    CredentialBuilder::new()
+        .id("urn:dephy:reading:42")
+        .credential_type("SensorReadingCredential")
+        .claim("sensor", "temperature")
+        .claim("value", 23.5)
+        .claim("unit", "Cel")
+        .claim("observedAt", 1700000000u64)
+        .issued_at(1700000000)
+}
+
+#[test]
+fn issue_and_check_credential() {
+    let token = reading_credential().issue(&signer()).unwrap();
+    let did = format!("did:dephy:0x{}", ADDR_HEX);
+
+    let jwt = Jwt::decode(&token).unwrap();
+    assert_eq!(jwt.header["alg"], "ES256K");
+    assert_eq!(jwt.header["kid"], format!("{}#key-1", did));
+
+    let (issuer, claims) = check_credential(&token, 1700000001).unwrap();
+    assert_eq!(hex::encode(issuer), ADDR_HEX);
+    assert_eq!(claims["iss"], did);
+    assert_eq!(claims["nbf"], 1700000000u64);
+    assert_eq!(claims["jti"], "urn:dephy:reading:42");
+    assert!(claims.get("exp").is_none());
+    assert_eq!(
+        claims["vc"],
+        json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiableCredential", "SensorReadingCredential"],
+            "credentialSubject": {
+                "sensor": "temperature",
+                "value": 23.5,
+                "unit": "Cel",
+                "observedAt": 1700000000u64,
+            },
+        })
+    );
+
+    assert!(CredentialBuilder::new().issue(&signer()).is_err());
+}
+
+#[test]
+fn rejects_invalid_credentials() {
+    let token = reading_credential()
+        .expires_at(1700003600)
+        .issue(&signer())
+        .unwrap();
+    check_credential(&token, 1700003599).unwrap();
+    assert!(check_credential(&token, 1700003600).is_err());
+    assert!(check_credential(&token, 1699999999).is_err());
+
+    // Signed by another key than the one of the issuer DID.
+    let other = SoftwareSigner::from_slice(&[1u8; 32]).unwrap();
+    let claims = Jwt::decode(&token).unwrap().claims;
+    let forged = sign_jwt_es256k(&other, json!({ "typ": "JWT" }), &claims).unwrap();
+    assert!(check_credential(&forged, 1700000001).is_err());
+
+    // A JWT of the issuer which is not a credential.
+    let jwt = sign_jwt_es256k(
+        &signer(),
+        json!({}),
+        &json!({ "iss": format!("did:dephy:0x{}", ADDR_HEX) }),
+    )
+    .unwrap();
+    assert!(check_credential(&jwt, 1700000001).is_err());
+}
@@ -1,15 +1,25 @@
 use crate::ble;
-use crate::crypto::{create_signed_message, MY_ADDRESS_STRING};
+use crate::crypto::{
+    device_message_builder, get_random_aux_rand, get_random_iv, MessageBuilder, DEVICE_SIGNER,
+    MY_ADDRESS_STRING,
+};
 use crate::http::request_text;
+use crate::http_server::start_http_server;
+use crate::nostr::{create_nostr_event, publish_nostr_event};
 use crate::peripherals::{
     create_timer_driver_00, take_gpio12_output, take_gpio13_output, take_i2c,
 };
 use crate::preludes::*;
+use crate::session::current_session;
+use crate::siwe::ensure_siwe_session;
+use crate::vc::{issue_reading_credential, publish_credential};
 use crate::wifi::{app_wifi_loop, MacList};
 use chrono::Utc;
+use dephy_message::cose::CONTENT_TYPE_COSE_SIGN1;
 use embedded_svc::http::Method;
 use esp32_nimble::BLEDevice;
 use esp_idf_svc::wifi::{AsyncWifi, EspWifi};
+use k256::PublicKey;
 use std::sync::Arc;
 use tokio::time::sleep;
 
@@ -19,6 +29,43 @@ pub static CELCIUS_CONVERSION: f32 = 0.00390625;
 #[derive(Clone)]
 pub struct AppContext {
     pub name: String,
+    pub encrypt_to: Option<PublicKey>,
+    pub w3b: Option<W3bstreamOptions>,
+    pub signature_scheme: SignatureScheme,
+}
+
+impl AppContext {
+    pub fn new(name: String) -> Result<Self> {
+        let encrypt_to = if APP_ENCRYPT_TO_PUBKEY.is_empty() {
+            None
+        } else {
+            Some(PublicKey::from_sec1_bytes(
+                hex::decode(APP_ENCRYPT_TO_PUBKEY.trim_start_matches("0x"))?.as_slice(),
+            )?)
+        };
+        let w3b = if W3B_TOPIC.is_empty() {
+            None
+        } else {
+            let encoding = W3bstreamPayloadEncoding::from_str_name(W3B_ENCODING).ok_or(anyhow!(
+                "Unknown W3bstream payload encoding: {}",
+                W3B_ENCODING
+            ))?;
+            Some(W3bstreamOptions {
+                topic: W3B_TOPIC.to_string(),
+                token: W3B_TOKEN.to_string(),
+                encoding: encoding.into(),
+            })
+        };
+        let signature_scheme = SignatureScheme::from_str_name(APP_SIGNATURE_SCHEME).ok_or(
+            anyhow!("Unknown signature scheme: {}", APP_SIGNATURE_SCHEME),
+        )?;
+        Ok(Self {
+            name,
+            encrypt_to,
+            w3b,
+            signature_scheme,
+        })
+    }
 }
 
 pub fn main_wrapper(wifi: AsyncWifi<EspWifi<'static>>, wifi_scan_result: MacList) -> Result<()> {
@@ -30,9 +77,17 @@ pub fn main_wrapper(wifi: AsyncWifi<EspWifi<'static>>, wifi_scan_result: MacList
     let name = wifi.wifi().sta_netif().get_mac()?;
     let name = format!("DePHY_{}", hex::encode(&name));
 
-    let ctx = AppContext { name };
+    let ctx = AppContext::new(name)?;
     let ctx = Arc::new(ctx);
 
+    let _http_server = match start_http_server() {
+        Ok(server) => Some(server),
+        Err(e) => {
+            error!("start_http_server: {}", e);
+            None
+        }
+    };
+
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
@@ -80,6 +135,7 @@ async fn main(ctx: Arc<AppContext>) -> Result<()> {
     let mut temperature = 0f32;
 
     loop {
+        let mut fresh = false;
         // I2C example getting temperature from Mysentech M117B sensor
         if let Err(e) = i2c.write_read(addr, &i2c_w_read, &mut i2c_buf, 50) {
             error!("i2c.write_read: {}", e);
@@ -88,11 +144,12 @@ async fn main(ctx: Arc<AppContext>) -> Result<()> {
             let temp = (temp as i16) as f32 * CELCIUS_CONVERSION;
             let temp = 40.0 + temp;
             temperature = temp;
+            fresh = true;
             info!("Temp: {}", temperature);
         }
 
         if cycle_count >= 30 {
-            if let Err(e) = publish_message(ctx.clone(), temperature).await {
+            if let Err(e) = publish_message(ctx.clone(), temperature, fresh).await {
                 error!("publish_message: {}", e);
                 info!("retrying in next cycle.")
             } else {
@@ -105,11 +162,44 @@ async fn main(ctx: Arc<AppContext>) -> Result<()> {
     }
 }
 
-async fn publish_message(ctx: Arc<AppContext>, temp: f32) -> Result<()> {
-    let body = format!("{},{}", ctx.name.as_str(), temp);
-    let body = body.as_bytes().to_vec();
-    let body = create_signed_message(body, None)?;
-    let body = body.encode_to_vec();
+/// Publishes `temp`, `fresh` when it was read in this cycle rather than kept from an
+/// earlier one.
+async fn publish_message(ctx: Arc<AppContext>, temp: f32, fresh: bool) -> Result<()> {
+    let body = match &ctx.w3b {
+        Some(w3b) if w3b.encoding() == W3bstreamPayloadEncoding::WpeUtf8Json => format!(
+            "{{\"name\":\"{}\",\"temperature\":{}}}",
+            ctx.name.as_str(),
+            temp
+        ),
+        _ => format!("{},{}", ctx.name.as_str(), temp),
+    };
+
+    // Taken once, the timestamp of the envelope is when the reading was observed.
+    let timestamp = Utc::now().timestamp() as u64;
+    let reading = device_message_builder(timestamp)?.payload(body.as_str());
+    let mut builder = reading.clone();
+    if let Some(peer) = ctx.encrypt_to.clone() {
+        builder = builder.encrypt_to(peer, get_random_iv());
+    }
+    if let Some(w3b) = ctx.w3b.clone() {
+        builder = builder.w3b(w3b);
+    }
+    if ctx.signature_scheme == SignatureScheme::SsBip340Schnorr {
+        builder = builder.schnorr(get_random_aux_rand());
+    }
+    let msg = if APP_SESSION_KEY_LIFETIME > 0 {
+        let (signer, cert) = current_session()?;
+        builder.delegation(cert).build(&signer)?
+    } else {
+        builder.build(&*DEVICE_SIGNER)?
+    };
+    let data = msg.encode_to_vec();
+
+    if !SIWE_LOGIN_URL.is_empty() {
+        if let Err(e) = ensure_siwe_session() {
+            error!("ensure_siwe_session: {}", e);
+        }
+    }
 
     let now = Utc::now();
     let now = now.to_rfc2822();
@@ -118,7 +208,7 @@ async fn publish_message(ctx: Arc<AppContext>, temp: f32) -> Result<()> {
         DEPHY_ENDPOINT_HTTP,
         Some(Method::Post),
         &[],
-        Some(body.as_slice()),
+        Some(data.as_slice()),
     ) {
         Ok(ret) => {
             if ret == RESPONSE_JSON_OK {
@@ -131,6 +221,31 @@ async fn publish_message(ctx: Arc<AppContext>, temp: f32) -> Result<()> {
             bail!("[{}] failed to publish message: {}", now.as_str(), e)
         }
     }
+
+    // Failed posts are retried in the next cycle, the reading is only published to
+    // other channels once it was accepted, on a best-effort basis.
+    if !NOSTR_RELAY_URL.is_empty() {
+        match create_nostr_event(body.as_str()).and_then(|e| publish_nostr_event(&e)) {
+            Ok(()) => info!("Published Nostr event to {}", NOSTR_RELAY_URL),
+            Err(e) => error!("publish_nostr_event: {}", e),
+        }
+    }
+    if !VC_ENDPOINT_HTTP.is_empty() && !fresh {
+        warn!("Temperature not read in this cycle, no reading credential issued");
+    } else if !VC_ENDPOINT_HTTP.is_empty() {
+        match issue_reading_credential("temperature", temp, "Cel", timestamp)
+            .and_then(|vc| publish_credential(&vc))
+        {
+            Ok(()) => info!("Published reading credential to {}", VC_ENDPOINT_HTTP),
+            Err(e) => error!("publish_credential: {}", e),
+        }
+    }
+    if !COSE_ENDPOINT_HTTP.is_empty() {
+        match build_cose_message(&ctx, reading).and_then(|m| publish_cose_message(&m)) {
+            Ok(()) => info!("Published COSE_Sign1 message to {}", COSE_ENDPOINT_HTTP),
+            Err(e) => error!("publish_cose_message: {}", e),
+        }
+    }
     Ok(())
 }
 
@@ -145,3 +260,24 @@ pub async fn ble_task(ctx: Arc<AppContext>) -> Result<()> {
     ble::ble_advertise_task(name, ble_server, ble_advertising).await;
     Ok(())
 }
+
+/// Signs the same reading as a COSE_Sign1 message with the device key, under the timestamp
+/// and nonce of its protobuf envelope. W3bstream options and session keys only apply to the
+/// protobuf envelope.
+fn build_cose_message(ctx: &AppContext, mut builder: MessageBuilder) -> Result<Vec<u8>> {
+    if let Some(peer) = ctx.encrypt_to.clone() {
+        builder = builder.encrypt_to(peer, get_random_iv());
+    }
+    builder.build_cose(&*DEVICE_SIGNER)
+}
+
+fn publish_cose_message(msg: &[u8]) -> Result<()> {
+    let ret = request_text(
+        COSE_ENDPOINT_HTTP,
+        Some(Method::Post),
+        &[("content-type", CONTENT_TYPE_COSE_SIGN1)],
+        Some(msg),
+    )?;
+    debug!("COSE endpoint: {}", ret);
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::did::device_did_document;
 use crate::preludes::*;
 use esp32_nimble::utilities::BleUuid;
 use esp32_nimble::{BLEAdvertising, BLEScan, BLEServer, NimbleProperties};
@@ -7,10 +8,15 @@ use tokio::time::sleep;
 
 pub static UUID_BLE_SERVICE_STR: &'static str = "io.dephy.ble"; // up-to 16 bytes
 pub static UUID_BLE_UPTIME_CHARA_STR: &'static str = "uptime"; // up-to 16 bytes
+pub static UUID_BLE_DID_LEN_CHARA_STR: &'static str = "did.len"; // up-to 16 bytes
+/// The DID document is split into parts in characteristics `did.0`, `did.1`... as attribute
+/// values are limited to 512 bytes.
+pub static BLE_DID_PART_SIZE: usize = 512;
 
 lazy_static! {
     pub static ref UUID_BLE_SERVICE: BleUuid = str_to_uuid(UUID_BLE_SERVICE_STR);
     pub static ref UUID_BLE_UPTIME_CHARA: BleUuid = str_to_uuid(UUID_BLE_UPTIME_CHARA_STR);
+    pub static ref UUID_BLE_DID_LEN_CHARA: BleUuid = str_to_uuid(UUID_BLE_DID_LEN_CHARA_STR);
 }
 
 pub fn str_to_uuid(s: &str) -> BleUuid {
@@ -47,6 +53,27 @@ pub async fn ble_advertise_task(
     );
     notifying_characteristic.lock().set_value(b"uptime: 0");
 
+    match device_did_document() {
+        Ok(doc) => {
+            service
+                .lock()
+                .create_characteristic(UUID_BLE_DID_LEN_CHARA.clone(), NimbleProperties::READ)
+                .lock()
+                .set_value(doc.len().to_string().as_bytes());
+            for (idx, part) in doc.as_bytes().chunks(BLE_DID_PART_SIZE).enumerate() {
+                service
+                    .lock()
+                    .create_characteristic(
+                        str_to_uuid(format!("did.{}", idx).as_str()),
+                        NimbleProperties::READ,
+                    )
+                    .lock()
+                    .set_value(part);
+            }
+        }
+        Err(e) => error!("device_did_document: {}", e),
+    }
+
     advertising
         .name(name)
         .add_service_uuid(UUID_BLE_SERVICE.clone());
@@ -1,24 +1,98 @@
+use crate::build_env::APP_MAX_CLOCK_SKEW;
+use crate::efuse::{get_identity_key, EFUSE_IDENTITIES};
+use crate::nonce::next_nonce;
 use crate::preludes::*;
+use dephy_key_inspect::{find_efuse_identity, DEVICE_IDENTITY};
+pub use dephy_message::auth::*;
+pub use dephy_message::crypto::*;
+pub use dephy_message::eth::*;
+pub use dephy_message::message::*;
+use dephy_message::replay::{MemoryReplayGuard, MessageChecker};
+pub use dephy_message::signer::*;
+pub use dephy_message::tx::*;
 use esp_idf_sys::esp_fill_random;
 use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
-use k256::SecretKey;
+use k256::{schnorr, PublicKey, SecretKey};
 use lazy_static::lazy_static;
-use sha3::{Digest, Keccak256};
+use parking_lot::Mutex;
 use std::ffi::c_void;
 
 lazy_static! {
-    pub static ref SECRET_KEY: SecretKey = get_device_secret_key().unwrap();
-    pub static ref MY_ADDRESS_BYTES: [u8; 20] =
-        get_eth_address_bytes(&SECRET_KEY.public_key().into());
+    pub static ref DEVICE_SIGNER: EfuseSigner = EfuseSigner::new().unwrap();
+    pub static ref MY_ADDRESS_BYTES: [u8; 20] = DEVICE_SIGNER.address_bytes();
     pub static ref MY_ADDRESS_STRING: String = hex::encode(MY_ADDRESS_BYTES.as_slice());
+    static ref INBOUND_CHECKER: Mutex<MessageChecker<MemoryReplayGuard>> = Mutex::new(
+        MessageChecker::new(MemoryReplayGuard::new(), APP_MAX_CLOCK_SKEW)
+    );
 }
 
-pub fn get_device_secret_key() -> Result<SecretKey> {
-    let buf = crate::key_inspect::get_key()?.ok_or(anyhow!("Key not provisionned"))?;
+/// The root key in eFuse of the identity named `identity`, which must not leave this
+/// module. Other modules use `EfuseSigner`.
+fn get_identity_secret_key(identity: &str) -> Result<SecretKey> {
+    let buf = get_identity_key(identity)?
+        .ok_or(anyhow!("Key of identity {} not provisionned", identity))?;
     Ok(SecretKey::from_slice(&buf)?)
 }
 
-#[allow(dead_code)]
+/// Signer backed by a key burnt in eFuse.
+///
+/// Only the public key is cached, the secret key is read from eFuse for every operation
+/// and dropped (thus zeroized) right after.
+pub struct EfuseSigner {
+    identity: &'static str,
+    verifying_key: VerifyingKey,
+}
+
+impl EfuseSigner {
+    /// Signer of the root key, which is the identity of the device.
+    pub fn new() -> Result<Self> {
+        Self::with_identity(DEVICE_IDENTITY)
+    }
+
+    /// Signer of another identity in `EFUSE_EXTRA_IDENTITIES`, like a test key.
+    pub fn identity(name: &str) -> Result<Self> {
+        let identity = find_efuse_identity(&EFUSE_IDENTITIES, name)?;
+        Self::with_identity(identity.name.as_str())
+    }
+
+    fn with_identity(identity: &'static str) -> Result<Self> {
+        let key = get_identity_secret_key(identity)?;
+        Ok(Self {
+            identity,
+            verifying_key: key.public_key().into(),
+        })
+    }
+
+    /// Name of the identity of the key.
+    #[allow(dead_code)]
+    pub fn identity_name(&self) -> &str {
+        self.identity
+    }
+}
+
+impl Signer for EfuseSigner {
+    fn verifying_key(&self) -> VerifyingKey {
+        self.verifying_key
+    }
+
+    fn sign_prehash_recoverable(&self, prehash: &[u8; 32]) -> Result<(Signature, RecoveryId)> {
+        let key: SigningKey = get_identity_secret_key(self.identity)?.into();
+        Ok(key.sign_prehash_recoverable(prehash)?)
+    }
+
+    fn sign_schnorr(&self, prehash: &[u8; 32], aux_rand: &[u8; 32]) -> Result<schnorr::Signature> {
+        let key: schnorr::SigningKey = get_identity_secret_key(self.identity)?.into();
+        Ok(key.sign_prehash_with_aux_rand(prehash, aux_rand)?)
+    }
+
+    fn shared_key(&self, peer: &PublicKey) -> Result<[u8; 32]> {
+        Ok(get_shared_key(
+            &get_identity_secret_key(self.identity)?,
+            peer,
+        ))
+    }
+}
+
 pub fn get_random_key() -> Result<SecretKey> {
     let buf = unsafe {
         let mut buf = [0u8; 32];
@@ -28,152 +102,91 @@ pub fn get_random_key() -> Result<SecretKey> {
     Ok(SecretKey::from_slice(&buf)?)
 }
 
-pub fn get_eth_address_bytes(key: &VerifyingKey) -> [u8; 20] {
-    let key = key.to_encoded_point(false);
-    let key = key.as_bytes();
-    let mut hasher = Keccak256::default();
-    hasher.update(&key[1..]);
-    let hash: [u8; 32] = hasher.finalize().into();
-    let addr = &hash[12..32];
-    addr.try_into().unwrap()
+pub fn get_random_iv() -> [u8; 16] {
+    let mut iv = [0u8; 16];
+    unsafe {
+        esp_fill_random(iv.as_mut_ptr() as *mut c_void, 16);
+    }
+    iv
 }
 
-pub fn get_eth_address(key: &VerifyingKey) -> String {
-    format!("0x{}", hex::encode(get_eth_address_bytes(key)))
+/// Auxiliary randomness of BIP-340 signatures.
+pub fn get_random_aux_rand() -> [u8; 32] {
+    let mut aux_rand = [0u8; 32];
+    unsafe {
+        esp_fill_random(aux_rand.as_mut_ptr() as *mut c_void, 32);
+    }
+    aux_rand
 }
 
-#[allow(dead_code)]
-pub fn did_str_to_addr_bytes<T: Into<String>>(did_str: T) -> Result<Vec<u8>> {
-    let did_str: String = did_str.into();
-    let did_str = did_str
-        .strip_prefix("did:dephy:0x")
-        .ok_or(anyhow!("Not in DID string format."))?;
-    if did_str.len() != 40 {
-        bail!("Invalid length for an DID string format.")
+/// Nonce of bearer tokens.
+pub fn get_random_token_nonce() -> [u8; 16] {
+    let mut nonce = [0u8; 16];
+    unsafe {
+        esp_fill_random(nonce.as_mut_ptr() as *mut c_void, 16);
     }
-    Ok(hex::decode(did_str)?)
-}
-
-pub fn create_signed_message(
-    payload: Vec<u8>,
-    to_address: Option<Vec<u8>>,
-) -> Result<SignedMessage> {
-    let signer: SigningKey = SECRET_KEY.clone().into();
-    let from_address = MY_ADDRESS_BYTES.to_vec();
-    let time = Utc::now();
-    let timestamp = time.timestamp() as u64;
-    let raw = RawMessage {
-        timestamp,
-        from_address,
-        to_address: if let Some(t) = to_address {
-            t
-        } else {
-            [0u8; 20].into()
-        },
-        encrypted: false,
-        payload,
-        iv: None,
-        w3b: None,
+    nonce
+}
+
+/// Bearer token authenticating the device to `audience`, valid for
+/// `APP_HTTP_TOKEN_LIFETIME` seconds.
+pub fn device_auth_token(audience: &str) -> Result<String> {
+    sign_auth_token(
+        &*DEVICE_SIGNER,
+        audience,
+        Utc::now().timestamp() as u64,
+        APP_HTTP_TOKEN_LIFETIME,
+        &get_random_token_nonce(),
+    )
+}
+
+/// `MessageBuilder` with `timestamp` and the next nonce of the device filled in.
+///
+/// With `APP_NONCE_IS_TIMESTAMP`, the nonce is the timestamp as older firmware did, for
+/// backends still checking that they are equal.
+pub fn device_message_builder(timestamp: u64) -> Result<MessageBuilder> {
+    let nonce = if APP_NONCE_IS_TIMESTAMP {
+        timestamp
+    } else {
+        next_nonce()?
     };
-    let raw = raw.encode_to_vec();
-    let mut hasher = Keccak256::new();
-    hasher.update(&raw);
-    hasher.update(timestamp.to_string().as_bytes());
-    let raw_hash = hasher.finalize_reset();
-    hasher.update(&raw_hash);
-    let (signature, recid) = signer.sign_digest_recoverable(hasher)?;
-    let mut sign_bytes = signature.to_vec();
-    sign_bytes.append(&mut vec![recid.to_byte()]);
-
-    Ok(SignedMessage {
-        raw,
-        hash: raw_hash.to_vec(),
-        nonce: timestamp,
-        signature: sign_bytes,
-        last_edge_addr: None,
-    })
+    Ok(MessageBuilder::new().timestamp(timestamp).nonce(nonce))
 }
 
+/// Verifies a relayed or inbound message and returns its payload, decrypted if it was
+/// encrypted to the device.
+///
+/// Messages with a nonce not greater than the last one accepted from the same sender, or
+/// with a timestamp too far from the device clock, are rejected.
 #[allow(dead_code)]
-pub fn check_message(data: &[u8]) -> Result<(SignedMessage, RawMessage)> {
-    ensure!(data.len() > 0, "Message should not be empty!");
-
-    let mut hasher = Keccak256::new();
-
-    let msg = SignedMessage::decode(data)?;
-    let SignedMessage {
-        raw,
-        hash,
-        nonce,
-        signature,
-        ..
-    } = msg.clone();
-    let raw = raw.as_slice();
-    let hash = hash.as_slice();
-    let hash_hex = hex::encode(hash);
-    hasher.update(raw);
-    hasher.update(nonce.to_string().as_bytes());
-    let curr_hash = hasher.finalize_reset();
-    ensure!(
-        hash == curr_hash.as_slice(),
-        "Hash verification failed: expected=0x{} current=0x{}",
-        hash_hex,
-        hex::encode(curr_hash)
-    );
-    debug!("Raw message hash: 0x{}", hash_hex);
-
-    let raw_msg = RawMessage::decode(raw)?;
-    let RawMessage {
-        timestamp,
-        from_address,
-        ..
-    } = raw_msg.clone();
-    ensure!(
-        nonce == timestamp,
-        "Message timestamp check failed: outer={} inner={}",
-        nonce,
-        timestamp
-    );
+pub fn check_inbound_message(data: &[u8]) -> Result<(SignedMessage, RawMessage, Vec<u8>)> {
+    INBOUND_CHECKER
+        .lock()
+        .check_and_decrypt(data, Utc::now().timestamp() as u64, &*DEVICE_SIGNER)
+}
 
-    let from_address = from_address.as_slice();
-    let from_address_hex = hex::encode(from_address);
-    let signature = signature.as_slice();
-    ensure!(signature.len() == 65, "Bad signature length!");
-    let r = &signature[0..32];
-    let s = &signature[32..64];
-    let v = &signature[64..];
-    debug!(
-        "R: 0x{}\nS: 0x{}\nV: 0x{}\nSigner address: 0x{}",
-        hex::encode(r),
-        hex::encode(s),
-        hex::encode(v),
-        from_address_hex,
-    );
-    let rs = Signature::try_from(&signature[0..64])?;
-    let v = RecoveryId::try_from(v[0])?;
-    hasher.update(hash);
-    let r_key = VerifyingKey::recover_from_digest(hasher, &rs, v)?;
-    let r_key_addr = get_eth_address_bytes(&r_key);
-    let r_key_addr = r_key_addr.as_ref();
-    ensure!(
-        from_address == r_key_addr.as_ref(),
-        "Signature check failed! expected_signer=0x{} actual_signer=0x{}",
-        from_address_hex,
-        hex::encode(r_key_addr)
-    );
-    debug!(
-        "Signer public key: 0x{}",
-        hex::encode(r_key.to_sec1_bytes())
-    );
-    debug!(
-        "Last touched: 0x{}",
-        if let Some(addr) = &msg.last_edge_addr {
-            let addr = addr.as_slice();
-            hex::encode(addr)
-        } else {
-            "None".to_string()
-        }
-    );
+/// EIP-191 `personal_sign` with the device key, verifiable with `ecrecover`.
+#[allow(dead_code)]
+pub fn device_personal_sign(message: &[u8]) -> Result<[u8; 65]> {
+    personal_sign(&*DEVICE_SIGNER, message)
+}
 
-    Ok((msg, raw_msg))
+/// EIP-712 signature with the device key, verifiable with `ecrecover`.
+#[allow(dead_code)]
+pub fn device_sign_typed_data(domain: &Eip712Domain, message: &Eip712Struct) -> Result<[u8; 65]> {
+    sign_typed_data(&*DEVICE_SIGNER, domain, message)
+}
+
+/// Signs an EIP-1559 transaction with the device key and returns the raw transaction, to
+/// be handed to a relay.
+#[allow(dead_code)]
+pub fn device_sign_eip1559_transaction(tx: &Eip1559Transaction) -> Result<Vec<u8>> {
+    tx.sign(&*DEVICE_SIGNER)
+}
+
+/// Signs a legacy transaction with the device key and returns the raw transaction, to be
+/// handed to a relay.
+#[allow(dead_code)]
+pub fn device_sign_legacy_transaction(tx: &LegacyTransaction) -> Result<Vec<u8>> {
+    tx.sign(&*DEVICE_SIGNER)
 }
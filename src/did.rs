@@ -0,0 +1,24 @@
+use crate::crypto::DEVICE_SIGNER;
+use crate::preludes::*;
+use dephy_message::did::{did_document, parse_did_services, DidService};
+use dephy_message::signer::Signer;
+
+/// Services of the device: the DePHY endpoint, the Nostr relay if configured, and those in
+/// `DID_SERVICES`.
+pub fn device_did_services() -> Result<Vec<DidService>> {
+    let mut services = vec![DidService::new(
+        "dephy",
+        "DePHYMessageEndpoint",
+        DEPHY_ENDPOINT_HTTP,
+    )];
+    if !NOSTR_RELAY_URL.is_empty() {
+        services.push(DidService::new("nostr", "NostrRelay", NOSTR_RELAY_URL));
+    }
+    services.extend(parse_did_services(DID_SERVICES)?);
+    Ok(services)
+}
+
+/// DID document of the device identity in JSON.
+pub fn device_did_document() -> Result<String> {
+    Ok(did_document(&DEVICE_SIGNER.verifying_key(), &device_did_services()?).to_string())
+}
@@ -0,0 +1,106 @@
+use crate::preludes::*;
+use dephy_key_inspect::{
+    find_efuse_identity, parse_efuse_identities, read_key_block, EfuseIdentity, EfuseStorage,
+    EntropySource, EFUSE_KEY_BLOCKS,
+};
+use esp_idf_sys::{
+    esp_efuse_batch_write_begin, esp_efuse_batch_write_cancel, esp_efuse_batch_write_commit,
+    esp_efuse_block_t, esp_efuse_block_t_EFUSE_BLK_KEY0, esp_efuse_desc_t,
+    esp_efuse_get_key_purpose, esp_efuse_key_block_unused, esp_efuse_read_field_blob,
+    esp_efuse_set_write_protect, esp_efuse_write_field_blob, esp_fill_random,
+};
+use lazy_static::lazy_static;
+use std::ffi::c_void;
+use std::ptr::null;
+
+lazy_static! {
+    /// Identities of the device from `EFUSE_KEY_BLOCK` and `EFUSE_EXTRA_IDENTITIES`, the
+    /// first one is the device identity.
+    pub static ref EFUSE_IDENTITIES: Vec<EfuseIdentity> = parse_efuse_identities(
+        EFUSE_KEY_BLOCK,
+        EFUSE_EXTRA_IDENTITIES,
+        EFUSE_RESERVED_BLOCKS
+    )
+    .expect("Bad eFuse identities");
+}
+
+/// The eFuse of the chip, through the ESP-IDF eFuse manager.
+pub struct EspEfuse;
+
+/// The hardware RNG, which is a true RNG while Wi-Fi or BLE is on.
+pub struct EspRng;
+
+fn key_block(block: usize) -> Result<esp_efuse_block_t> {
+    ensure!(block < EFUSE_KEY_BLOCKS, "Bad eFuse key block: {}", block);
+    Ok(esp_efuse_block_t_EFUSE_BLK_KEY0 + block as esp_efuse_block_t)
+}
+
+/// Descriptor of the 256 bits of a key block.
+fn key_desc(block: esp_efuse_block_t) -> esp_efuse_desc_t {
+    let mut desc = esp_efuse_desc_t::default();
+    desc.set_efuse_block(block);
+    desc.bit_start = 0;
+    desc.bit_count = 256;
+    desc
+}
+
+impl EfuseStorage for EspEfuse {
+    fn key_block_unused(&self, block: usize) -> bool {
+        match key_block(block) {
+            Ok(block) => unsafe { esp_efuse_key_block_unused(block) },
+            Err(_) => false,
+        }
+    }
+
+    fn key_purpose(&self, block: usize) -> Result<u8> {
+        let block = key_block(block)?;
+        Ok(unsafe { esp_efuse_get_key_purpose(block) } as u8)
+    }
+
+    fn read_key(&self, block: usize) -> Result<[u8; 32]> {
+        let desc = key_desc(key_block(block)?);
+        let mut field = [&desc as *const esp_efuse_desc_t, null()];
+        let mut buf = [0u8; 32];
+        unsafe {
+            esp!(esp_efuse_read_field_blob(
+                field.as_mut_ptr(),
+                buf.as_mut_ptr() as *mut c_void,
+                256
+            ))?;
+        }
+        Ok(buf)
+    }
+
+    fn write_key(&mut self, block: usize, key: &[u8; 32]) -> Result<()> {
+        let block = key_block(block)?;
+        let desc = key_desc(block);
+        let mut field = [&desc as *const esp_efuse_desc_t, null()];
+        unsafe {
+            esp!(esp_efuse_batch_write_begin())?;
+            let ret = esp!(esp_efuse_write_field_blob(
+                field.as_mut_ptr(),
+                key.as_ptr() as *const c_void,
+                256
+            ))
+            .and_then(|_| esp!(esp_efuse_set_write_protect(block)));
+            if let Err(e) = ret {
+                esp!(esp_efuse_batch_write_cancel())?;
+                bail!("Failed to write eFuse key block: {}", e);
+            }
+            esp!(esp_efuse_batch_write_commit())?;
+        }
+        Ok(())
+    }
+}
+
+/// Key of the identity named `name`, `None` when it's not generated yet.
+pub fn get_identity_key(name: &str) -> Result<Option<[u8; 32]>> {
+    let identity = find_efuse_identity(&EFUSE_IDENTITIES, name)?;
+    read_key_block(&EspEfuse, identity.block)
+}
+
+impl EntropySource for EspRng {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        unsafe { esp_fill_random(buf.as_mut_ptr() as *mut c_void, buf.len()) };
+    }
+}
@@ -1,4 +1,6 @@
+use crate::crypto::{device_auth_token, url_audience};
 use crate::preludes::*;
+use crate::siwe::session_header;
 use embedded_svc::http::client::Client as HttpClient;
 use embedded_svc::http::Method;
 use embedded_svc::io::Write;
@@ -9,8 +11,10 @@ use esp_idf_svc::http::client::{
 use esp_idf_sys::esp_crt_bundle_attach;
 use std::vec::Vec;
 
+static USER_AGENT: &'static str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
 static COMMON_HEADERS: &'static [(&'static str, &'static str); 3] = &[
-    ("User-Agent", "curl/8.1.2"),
+    ("User-Agent", USER_AGENT),
     ("accept", "*/*"),
     ("content-type", "application/x-dephy"),
 ];
@@ -35,20 +39,40 @@ pub fn create_default_client() -> Result<HttpClient<EspHttpConnection>> {
     Ok(HttpClient::wrap(http))
 }
 
+/// Response of `request_response`, the body is truncated to 2048 bytes.
+pub struct HttpResponse {
+    pub status: u16,
+    pub set_cookie: Option<String>,
+    pub body: String,
+}
+
 pub fn request_text<'a>(
     url: &str,
     method: Option<Method>,
     user_headers: &[(&str, &str)],
     body_buf: Option<&'a [u8]>,
 ) -> Result<String> {
-    let (buf, bytes_read) = request(url, method, user_headers, body_buf)?;
+    Ok(request_response(url, method, user_headers, body_buf)?.body)
+}
+
+pub fn request_response<'a>(
+    url: &str,
+    method: Option<Method>,
+    user_headers: &[(&str, &str)],
+    body_buf: Option<&'a [u8]>,
+) -> Result<HttpResponse> {
+    let (status, set_cookie, buf, bytes_read) = request(url, method, user_headers, body_buf)?;
     let buf = &buf[..bytes_read];
     let ret = std::str::from_utf8(&buf)?;
     debug!(
         "Response body (truncated to {} bytes): {:?}",
         bytes_read, ret
     );
-    Ok(ret.to_string())
+    Ok(HttpResponse {
+        status,
+        set_cookie,
+        body: ret.to_string(),
+    })
 }
 
 fn request<'a>(
@@ -56,7 +80,7 @@ fn request<'a>(
     method: Option<Method>,
     user_headers: &[(&'a str, &'a str)],
     body_buf: Option<&'a [u8]>,
-) -> Result<([u8; 2048], usize)> {
+) -> Result<(u16, Option<String>, [u8; 2048], usize)> {
     let mut headers = Vec::new();
     headers.extend(COMMON_HEADERS.clone().into_iter());
 
@@ -67,6 +91,23 @@ fn request<'a>(
     let e = [("content-length", &len[..])];
     headers.extend(e.clone().into_iter());
 
+    // The SIWE session replaces the bearer token of the device when it is a token as well.
+    let session = session_header(url);
+    if let Some((name, value)) = session.as_ref() {
+        headers.push((*name, value.as_str()));
+    }
+    let has_session_token = matches!(session, Some(("authorization", _)));
+
+    let authorization = match token_audience(url)? {
+        Some(audience) if !has_session_token => {
+            Some(format!("Bearer {}", device_auth_token(&audience)?))
+        }
+        _ => None,
+    };
+    if let Some(authorization) = authorization.as_deref() {
+        headers.push(("authorization", authorization));
+    }
+
     headers.extend_from_slice(user_headers);
 
     let h = headers.clone();
@@ -86,10 +127,27 @@ fn request<'a>(
     // Process response
     let status = response.status();
     debug!("<- {}", status);
+    let set_cookie = response.header("set-cookie").map(ToString::to_string);
     let (_headers, mut body) = response.split();
     let mut buf = [0u8; 2048];
     let bytes_read = io::try_read_full(&mut body, &mut buf).map_err(|e| e.0)?;
     debug!("Read {} bytes", bytes_read);
 
-    Ok((buf, bytes_read))
+    Ok((status, set_cookie, buf, bytes_read))
+}
+
+/// Audience of the bearer token sent to `url`, if any.
+///
+/// Tokens for `APP_HTTP_TOKEN_AUDIENCE` are only sent to the origin of
+/// `DEPHY_ENDPOINT_HTTP`, any other host could replay them to the backend.
+fn token_audience(url: &str) -> Result<Option<String>> {
+    Ok(if APP_HTTP_TOKEN_LIFETIME == 0 {
+        None
+    } else if APP_HTTP_TOKEN_AUDIENCE.is_empty() {
+        Some(url_audience(url)?)
+    } else if url_audience(url)? == url_audience(DEPHY_ENDPOINT_HTTP)? {
+        Some(APP_HTTP_TOKEN_AUDIENCE.to_string())
+    } else {
+        None
+    })
 }
@@ -0,0 +1,23 @@
+use crate::did::device_did_document;
+use crate::preludes::*;
+use embedded_svc::http::Method;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::{Configuration, EspHttpServer};
+
+/// Starts the HTTP API of the device, the server stops when dropped.
+///
+/// - `GET /did.json`: DID document of the device.
+pub fn start_http_server() -> Result<EspHttpServer<'static>> {
+    let mut server = EspHttpServer::new(&Configuration {
+        http_port: APP_HTTP_SERVER_PORT,
+        ..Default::default()
+    })?;
+    server.fn_handler("/did.json", Method::Get, |req| {
+        let doc = device_did_document()?;
+        req.into_response(200, None, &[("content-type", "application/did+json")])?
+            .write_all(doc.as_bytes())?;
+        Ok(())
+    })?;
+    info!("HTTP API listening on port {}", APP_HTTP_SERVER_PORT);
+    Ok(server)
+}
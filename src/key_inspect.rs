@@ -1,25 +1,19 @@
-use crate::app::AppContext;
-use crate::crypto::get_eth_address;
+use crate::efuse::{get_identity_key, EspEfuse, EspRng, EFUSE_IDENTITIES};
 use crate::peripherals::{take_gpio12_output, take_gpio13_output};
 use crate::preludes::*;
+use crate::serial::{set_key_inspect_status, start_serial_server};
+use dephy_key_inspect::{
+    EntropyHealth, EntropySource, EntropyWaitPolicy, KeyInspect, KeyInspectStatus, DEVICE_IDENTITY,
+};
 use esp32_nimble::BLEDevice;
 use esp_idf_hal::delay::FreeRtos;
 use esp_idf_hal::gpio::{Output, Pin, PinDriver};
 use esp_idf_hal::task::block_on;
 use esp_idf_svc::wifi::EspWifi;
-use esp_idf_sys::{
-    esp_efuse_batch_write_begin, esp_efuse_batch_write_commit, esp_efuse_block_t_EFUSE_BLK_KEY4,
-    esp_efuse_desc_t, esp_efuse_get_field_size, esp_efuse_key_block_unused,
-    esp_efuse_read_field_blob, esp_efuse_set_write_protect, esp_efuse_write_field_blob,
-    esp_fill_random,
-};
-use k256::SecretKey;
-use std::ffi::c_void;
-use std::ptr::null;
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub fn main(mut wifi: EspWifi<'static>) -> Result<()> {
+/// Runs the key inspect mode, keys of missing extra identities are only generated with
+/// `provision_extra_identities`, when the mode was requested with the button.
+pub fn main(mut wifi: EspWifi<'static>, provision_extra_identities: bool) -> Result<()> {
     // Initializing Wi-Fi and BLE to collect entropy for hardware RNG
     wifi.start()?;
 
@@ -34,29 +28,17 @@ pub fn main(mut wifi: EspWifi<'static>) -> Result<()> {
         .expect("wifi.wifi().sta_netif().get_mac()");
     let name = format!("DePHY_{}", hex::encode(&name));
 
-    let ctx = AppContext { name };
-    let ctx = Arc::new(ctx);
-
-    key_loop(ctx.name.clone(), led1, led2)?;
+    // Only the name is needed here, app settings must not stop a blank device from
+    // being provisioned.
+    start_serial_server(name.clone());
+    key_loop(name, provision_extra_identities, led1, led2)?;
 
     Ok(())
 }
 
-enum KeyInspectStatus {
-    Init,
-    WaitingForEntropy {
-        secs_waited: u64,
-    },
-    ShouldGenerateKey,
-    KeyTaken {
-        pubkey_hex: String,
-        addr_hex: String,
-        secs_waited: u64,
-    },
-}
-
 fn key_loop<'a, T1: Pin, T2: Pin>(
     name: String,
+    provision_extra_identities: bool,
     mut led1: PinDriver<'a, T1, Output>,
     mut led2: PinDriver<'a, T2, Output>,
 ) -> Result<()> {
@@ -74,65 +56,52 @@ fn key_loop<'a, T1: Pin, T2: Pin>(
     info!("Key inspect mode!");
     FreeRtos::delay_ms(3000);
 
+    let policy = EntropyWaitPolicy {
+        max_wait_secs: ENTROPY_MAX_WAIT_SECS,
+        min_wait_secs: ENTROPY_MIN_WAIT_SECS,
+        min_healthy_samples: ENTROPY_MIN_SAMPLES,
+    };
+    let mut inspect = KeyInspect::new(
+        EspEfuse,
+        EspRng,
+        EntropyHealth::new(ENTROPY_MIN_BITS),
+        policy,
+        EFUSE_IDENTITIES.clone(),
+    );
+    inspect.provision_extra_identities = provision_extra_identities;
     let mut s = KeyInspectStatus::Init;
 
     loop {
-        let wait_secs = match s {
-            KeyInspectStatus::Init => {
-                if let Some(buf) = get_key()? {
-                    let key = SecretKey::from_slice(&buf)?;
-                    let key = key.public_key();
-                    let pubkey_hex = hex::encode(key.to_sec1_bytes());
-                    let addr_hex = get_eth_address(&key.into());
-                    s = KeyInspectStatus::KeyTaken {
-                        pubkey_hex,
-                        addr_hex,
-                        secs_waited: 0,
-                    };
-                } else {
-                    s = KeyInspectStatus::WaitingForEntropy { secs_waited: 1 };
-                }
-                1
+        s = inspect.next(s)?;
+        let progress = match &s {
+            KeyInspectStatus::Init => 0,
+            KeyInspectStatus::WaitingForEntropy { secs_waited } => {
+                policy.progress(*secs_waited, &inspect.health)
             }
+            _ => 100,
+        };
+        set_key_inspect_status(&s, progress);
+
+        match &s {
             KeyInspectStatus::WaitingForEntropy { secs_waited } => {
+                let secs_waited = *secs_waited;
                 info!(
-                    "Have been waiting for {} seconds for entropy...",
-                    secs_waited
+                    "Waiting for entropy: {}% ({} seconds, {} healthy samples)...",
+                    progress, secs_waited, inspect.health.healthy_samples
                 );
-                let next = secs_waited + 1;
 
-                if next % 2 == 1 {
+                // LED2 blinks every second, LED1 is lit for one second of every ten per
+                // 10% of progress.
+                if secs_waited % 10 < progress as u64 / 10 {
+                    led1.set_high()?;
+                } else {
                     led1.set_low()?;
+                }
+                if secs_waited % 2 == 1 {
                     led2.set_high()?;
                 } else {
-                    led1.set_high()?;
                     led2.set_low()?;
                 }
-
-                if next > 3600 {
-                    // if next > 2 {
-                    s = KeyInspectStatus::ShouldGenerateKey;
-                } else {
-                    s = KeyInspectStatus::WaitingForEntropy { secs_waited: next };
-                }
-                1
-            }
-            KeyInspectStatus::ShouldGenerateKey => {
-                info!("Should generate key now!",);
-                write_key()?;
-                let buf = get_key()?.unwrap();
-                let key = SecretKey::from_slice(&buf)?;
-                let key = key.public_key();
-                let pubkey_hex = hex::encode(key.to_sec1_bytes());
-                let addr_hex = get_eth_address(&key.into());
-
-                s = KeyInspectStatus::KeyTaken {
-                    pubkey_hex,
-                    addr_hex,
-                    secs_waited: 0,
-                };
-
-                1
             }
             KeyInspectStatus::KeyTaken {
                 pubkey_hex,
@@ -145,10 +114,12 @@ fn key_loop<'a, T1: Pin, T2: Pin>(
                     info!("addr_hex: {}", addr_hex.as_str());
 
                     println!(
-                        "\n\n{{\"device_name\":\"{}\",\"pubkey_hex\":\"{}\",\"addr_hex\":\"{}\"}}\n\n", 
+                        "\n\n{{\"device_name\":\"{}\",\"pubkey_hex\":\"{}\",\"addr_hex\":\"{}\",\"identities\":{},\"entropy_health\":{}}}\n\n",
                         &name,
                         pubkey_hex.as_str(),
-                        addr_hex.as_str()
+                        addr_hex.as_str(),
+                        identities_json(&inspect)?,
+                        entropy_health_json(&inspect.health)
                     );
                 }
 
@@ -159,73 +130,44 @@ fn key_loop<'a, T1: Pin, T2: Pin>(
                     led1.set_low()?;
                     led2.set_low()?;
                 }
-
-                s = KeyInspectStatus::KeyTaken {
-                    pubkey_hex,
-                    addr_hex,
-                    secs_waited: secs_waited + 1,
-                };
-
-                1
             }
-        };
-        FreeRtos::delay_ms(wait_secs * 1000);
+            KeyInspectStatus::Init | KeyInspectStatus::ShouldGenerateKey { .. } => {}
+        }
+        FreeRtos::delay_ms(1000);
     }
 }
 
-pub fn get_key() -> Result<Option<[u8; 32]>> {
-    unsafe {
-        if esp_efuse_key_block_unused(esp_efuse_block_t_EFUSE_BLK_KEY4) {
-            info!("esp_efuse_block_t_EFUSE_BLK_KEY4 not used.");
-            return Ok(None);
-        }
-        let mut desc4 = esp_efuse_desc_t::default();
-        desc4.set_efuse_block(esp_efuse_block_t_EFUSE_BLK_KEY4);
-        desc4.bit_start = 0;
-        desc4.bit_count = 256;
-
-        let mut desc4 = [&desc4 as *const esp_efuse_desc_t, null()];
-        let desc4 = desc4.as_mut_ptr();
-
-        let size4 = esp_efuse_get_field_size(desc4);
-        info!("size4: {}", size4);
-        if size4 == 0 {
-            return Ok(None);
-        }
-        let mut buf = [0u8; 32];
-        esp!(esp_efuse_read_field_blob(
-            desc4,
-            buf.as_mut_ptr() as *mut c_void,
-            256
-        ))?;
-        return Ok(Some(buf));
-    }
+/// Whether the device identity holds a key, otherwise the key inspect mode generates it.
+pub fn device_key_provisioned() -> Result<bool> {
+    Ok(get_identity_key(DEVICE_IDENTITY)?.is_some())
 }
 
-fn write_key() -> Result<()> {
-    unsafe {
-        let mut buf = [0u8; 32];
-        esp_fill_random(buf.as_mut_ptr() as *mut c_void, 32);
-
-        let mut desc4 = esp_efuse_desc_t::default();
-        desc4.set_efuse_block(esp_efuse_block_t_EFUSE_BLK_KEY4);
-        desc4.bit_start = 0;
-        desc4.bit_count = 256;
-
-        let mut desc4 = [&desc4 as *const esp_efuse_desc_t, null()];
-        let desc4 = desc4.as_mut_ptr();
-
-        esp!(esp_efuse_batch_write_begin())?;
-        esp!(esp_efuse_write_field_blob(
-            desc4,
-            buf.as_ptr() as *const c_void,
-            256
-        ))?;
-        esp!(esp_efuse_set_write_protect(
-            esp_efuse_block_t_EFUSE_BLK_KEY4
-        ))?;
-        esp!(esp_efuse_batch_write_commit())?;
-        info!("Random key has been written to eFuse!");
-    }
-    Ok(())
+/// Identities holding a key in the key inspect output, like
+/// `[{"name":"device","block":4,"addr_hex":"0x..."}]`.
+fn identities_json<R: EntropySource>(inspect: &KeyInspect<EspEfuse, R>) -> Result<String> {
+    let identities = inspect
+        .identity_addresses()?
+        .into_iter()
+        .map(|(identity, addr_hex)| {
+            format!(
+                "{{\"name\":\"{}\",\"block\":{},\"addr_hex\":\"{}\"}}",
+                identity.name, identity.block, addr_hex
+            )
+        })
+        .collect::<Vec<_>>();
+    Ok(format!("[{}]", identities.join(",")))
+}
+
+/// Health test results in the key inspect output. Zero samples means that the key was
+/// already in eFuse when the device booted.
+fn entropy_health_json(health: &EntropyHealth) -> String {
+    format!(
+        "{{\"healthy\":{},\"samples\":{},\"rct_cutoff\":{},\"rct_failures\":{},\"apt_cutoff\":{},\"apt_failures\":{}}}",
+        health.is_healthy(),
+        health.samples,
+        health.rct_cutoff(),
+        health.rct_failures,
+        health.apt_cutoff(),
+        health.apt_failures
+    )
 }
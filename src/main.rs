@@ -1,4 +1,4 @@
-use crate::key_inspect::get_key;
+use crate::key_inspect::device_key_provisioned;
 use crate::peripherals::{
     create_esp_wifi, patch_eventfd, take_gpio12_output, take_gpio13_output, take_gpio9_input,
     ESP_TASK_TIMER_SVR, SYS_LOOP,
@@ -16,13 +16,22 @@ mod app;
 mod ble;
 mod build_env;
 mod crypto;
+mod did;
+mod efuse;
 mod http;
+mod http_server;
 mod key_inspect;
 mod mqtt;
+mod nonce;
+mod nostr;
 mod ntp;
 mod peripherals;
 mod preludes;
 mod proto;
+mod serial;
+mod session;
+mod siwe;
+mod vc;
 mod wifi;
 
 fn main() {
@@ -32,17 +41,24 @@ fn main() {
 
     let mut wifi = create_esp_wifi();
 
-    let key = get_key().unwrap();
-    if key.is_none() {
-        key_inspect::main(wifi).expect("key_inspect_main");
-        return;
+    match device_key_provisioned() {
+        Ok(true) => {}
+        Ok(false) => {
+            key_inspect::main(wifi, false).expect("key_inspect_main");
+            return;
+        }
+        Err(e) => {
+            // Restarting would fail the same way, the key block needs to be looked at.
+            error!("Failed to read the device key: {}", e);
+            return;
+        }
     }
 
     let boot_type = get_boot_type().expect("get_boot_type failed");
     info!("boot_type: {:?}", &boot_type);
 
     if BootType::KeyInspectMode == boot_type {
-        key_inspect::main(wifi).expect("key_inspect_main");
+        key_inspect::main(wifi, true).expect("key_inspect_main");
         return;
     }
 
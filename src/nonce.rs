@@ -0,0 +1,76 @@
+use crate::peripherals::NVS_DEFAULT_PARTITION;
+use crate::preludes::*;
+use esp_idf_svc::nvs::EspDefaultNvs;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+static NVS_NAMESPACE: &'static str = "dephy";
+static NVS_KEY_NONCE_RESERVED: &'static str = "nonce_rsvd"; // up-to 15 bytes
+
+// 2023-01-01T00:00:00Z, the clock reads 1970 until `ntp_sync` sets it.
+const MIN_CLOCK_TIMESTAMP: u64 = 1_672_531_200;
+
+lazy_static! {
+    /// Opened on first use, and again on the next call if opening failed.
+    static ref NONCE_COUNTER: Mutex<Option<NonceCounter>> = Mutex::new(None);
+}
+
+/// Monotonic counter backing `SignedMessage.nonce`.
+///
+/// Instead of writing every nonce to flash, a batch of `APP_NONCE_BATCH_SIZE` nonces is
+/// reserved in NVS at once. After a reboot the counter resumes from the end of the last
+/// reserved batch, so nonces never go backwards even if some of them were never used.
+///
+/// The counter never starts below the current timestamp: nonces used to be timestamps,
+/// and still are with `APP_NONCE_IS_TIMESTAMP`, so devices upgraded from older firmware or
+/// switched back from timestamp nonces keep sending increasing nonces.
+struct NonceCounter {
+    nvs: EspDefaultNvs,
+    next: u64,
+    reserved: u64,
+}
+
+impl NonceCounter {
+    fn new() -> Result<Self> {
+        let nvs = EspDefaultNvs::new(NVS_DEFAULT_PARTITION.clone(), NVS_NAMESPACE, true)?;
+        let reserved = nvs.get_u64(NVS_KEY_NONCE_RESERVED)?.unwrap_or(0);
+        let next = reserved.max(clock_timestamp()?);
+        info!("Nonce counter starts from {}", next);
+        Ok(Self {
+            nvs,
+            next,
+            reserved: next,
+        })
+    }
+
+    fn take(&mut self) -> Result<u64> {
+        if self.next >= self.reserved {
+            let reserved = self.next + APP_NONCE_BATCH_SIZE.max(1);
+            self.nvs.set_u64(NVS_KEY_NONCE_RESERVED, reserved)?;
+            debug!("Reserved nonces up to {}", reserved);
+            self.reserved = reserved;
+        }
+        let ret = self.next;
+        self.next += 1;
+        Ok(ret)
+    }
+}
+
+/// Current timestamp of the device clock, failing while the clock is not set yet.
+fn clock_timestamp() -> Result<u64> {
+    let now = Utc::now().timestamp().max(0) as u64;
+    ensure!(
+        now >= MIN_CLOCK_TIMESTAMP,
+        "Device clock is not set: timestamp={}",
+        now
+    );
+    Ok(now)
+}
+
+pub fn next_nonce() -> Result<u64> {
+    let mut counter = NONCE_COUNTER.lock();
+    if counter.is_none() {
+        *counter = Some(NonceCounter::new()?);
+    }
+    counter.as_mut().unwrap().take()
+}
@@ -0,0 +1,76 @@
+use crate::crypto::{get_random_aux_rand, DEVICE_SIGNER};
+use crate::preludes::*;
+use dephy_message::nostr::{parse_relay_ok, NostrEvent, NostrEventBuilder};
+use embedded_svc::ws::FrameType;
+use esp_idf_svc::ws::client::{
+    EspWebSocketClient, EspWebSocketClientConfig, EspWebSocketConnection, WebSocketEventType,
+};
+
+static RELAY_TIMEOUT: Duration = Duration::from_secs(18);
+
+/// Signs `content` as a Nostr event with the BIP-340 key of the device.
+pub fn create_nostr_event(content: &str) -> Result<NostrEvent> {
+    NostrEventBuilder::new(NOSTR_EVENT_KIND)
+        .created_at(Utc::now().timestamp() as u64)
+        .content(content)
+        .build(&*DEVICE_SIGNER, &get_random_aux_rand())
+}
+
+/// Publishes `event` to the relay at `NOSTR_RELAY_URL` and waits for it to be accepted.
+pub fn publish_nostr_event(event: &NostrEvent) -> Result<()> {
+    let config = EspWebSocketClientConfig {
+        use_global_ca_store: true,
+        ..Default::default()
+    };
+    let (mut client, mut conn) =
+        EspWebSocketClient::new_with_conn(NOSTR_RELAY_URL, &config, RELAY_TIMEOUT)?;
+
+    wait_for_event(&mut conn, |e| matches!(e, WebSocketEventType::Connected))?;
+    debug!("-> EVENT {}", hex::encode(event.id));
+    client.send(FrameType::Text(false), event.to_relay_message().as_bytes())?;
+
+    let mut ret = Err(anyhow!("Relay closed the connection without OK!"));
+    wait_for_event(&mut conn, |e| match e {
+        WebSocketEventType::Text(msg) => match parse_relay_ok(msg) {
+            Ok(Some((id, accepted, msg))) if id == event.id => {
+                debug!("<- OK {} {}", accepted, msg);
+                ret = if accepted {
+                    Ok(())
+                } else {
+                    Err(anyhow!("Relay rejected the event: {}", msg))
+                };
+                true
+            }
+            Ok(_) => {
+                debug!("<- {}", msg);
+                false
+            }
+            Err(e) => {
+                warn!("{}", e);
+                false
+            }
+        },
+        _ => false,
+    })?;
+    ret
+}
+
+/// Blocks until `f` returns `true` for an event, or the connection is closed.
+fn wait_for_event<F>(conn: &mut EspWebSocketConnection, mut f: F) -> Result<()>
+where
+    F: FnMut(&WebSocketEventType) -> bool,
+{
+    while let Some(event) = conn.next() {
+        let event = event?;
+        if f(&event.event_type) {
+            return Ok(());
+        }
+        match event.event_type {
+            WebSocketEventType::Disconnected
+            | WebSocketEventType::Close(_)
+            | WebSocketEventType::Closed => bail!("Disconnected from the relay!"),
+            _ => {}
+        }
+    }
+    bail!("Disconnected from the relay!")
+}
@@ -4,7 +4,7 @@ use esp_idf_hal::i2c::{config::Config as I2cConfig, I2cDriver};
 use esp_idf_hal::peripheral::Peripheral;
 use esp_idf_hal::peripherals::Peripherals;
 use esp_idf_hal::timer::{TimerConfig, TimerDriver};
-use esp_idf_hal::uart::{AsyncUartRxDriver, UartConfig, UartRxDriver};
+use esp_idf_hal::uart::{AsyncUartRxDriver, UartConfig, UartDriver, UartRxDriver};
 use esp_idf_hal::units::Hertz;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
@@ -154,3 +154,24 @@ pub fn take_uart() -> AsyncUartRxDriver<'static, UartRxDriver<'static>> {
     )
     .unwrap()
 }
+
+/// The console UART, which also carries the serial protocol of the key inspect mode.
+pub fn take_console_uart() -> UartDriver<'static> {
+    let p = PERIPHERALS.clone();
+    let mut p = p.lock();
+    let uart = unsafe { p.uart0.clone_unchecked() };
+    let tx = unsafe { p.pins.gpio21.clone_unchecked() };
+    let rx = unsafe { p.pins.gpio20.clone_unchecked() };
+    drop(p);
+
+    let conf = UartConfig::new().baudrate(Hertz(115_200));
+    UartDriver::new(
+        uart,
+        tx,
+        rx,
+        Option::<AnyIOPin>::None,
+        Option::<AnyIOPin>::None,
+        &conf,
+    )
+    .unwrap()
+}
@@ -1 +1 @@
-include!(concat!(env!("OUT_DIR"), "/dephy.message.rs"));
+pub use dephy_message::proto::*;
@@ -0,0 +1,83 @@
+use crate::crypto::{get_eth_address_bytes, personal_sign, EfuseSigner, Signer};
+use crate::efuse::EFUSE_IDENTITIES;
+use crate::peripherals::take_console_uart;
+use crate::preludes::*;
+use dephy_key_inspect::{
+    challenge_message, find_efuse_identity, handle_serial_packet, FirmwareInfo, FrameDecoder,
+    IdentityInfo, KeyInspectStatus, SerialDevice,
+};
+use esp_idf_hal::delay::TickType;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::thread;
+
+lazy_static! {
+    /// Status of the key inspect mode and progress of the entropy wait, as reported by
+    /// get-firmware-info.
+    static ref KEY_INSPECT_STATUS: Mutex<(&'static str, u8)> =
+        Mutex::new((KeyInspectStatus::Init.name(), 0));
+}
+
+pub fn set_key_inspect_status(status: &KeyInspectStatus, progress: u8) {
+    *KEY_INSPECT_STATUS.lock() = (status.name(), progress);
+}
+
+struct EspSerialDevice {
+    device_name: String,
+}
+
+impl SerialDevice for EspSerialDevice {
+    fn identity(&self, name: &str) -> Result<IdentityInfo> {
+        let identity = find_efuse_identity(&EFUSE_IDENTITIES, name)?;
+        let key = EfuseSigner::identity(name)?.verifying_key();
+        Ok(IdentityInfo {
+            name: identity.name.clone(),
+            block: identity.block,
+            public_key: key.to_sec1_bytes().to_vec(),
+            address: get_eth_address_bytes(&key),
+        })
+    }
+
+    fn sign_challenge(&self, name: &str, challenge: &[u8]) -> Result<[u8; 65]> {
+        let signer = EfuseSigner::identity(name)?;
+        personal_sign(&signer, challenge_message(challenge).as_bytes())
+    }
+
+    fn firmware_info(&self) -> FirmwareInfo {
+        let (status, progress) = *KEY_INSPECT_STATUS.lock();
+        FirmwareInfo {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            device_name: self.device_name.clone(),
+            status: status.to_string(),
+            progress,
+        }
+    }
+}
+
+/// Serves requests of provisioning stations on the console UART, between the log lines.
+pub fn start_serial_server(device_name: String) {
+    thread::spawn(move || {
+        if let Err(e) = serve(EspSerialDevice { device_name }) {
+            error!("Serial server stopped: {}", e);
+        }
+    });
+}
+
+fn serve(device: EspSerialDevice) -> Result<()> {
+    let uart = take_console_uart();
+    let timeout = TickType::from(Duration::from_millis(10)).ticks();
+    let mut decoder = FrameDecoder::new();
+    let mut buf = [0u8; 64];
+    loop {
+        let len = uart.read(&mut buf, timeout)?;
+        for &b in &buf[..len] {
+            let Some(packet) = decoder.push(b) else {
+                continue;
+            };
+            if let Some(res) = handle_serial_packet(&device, &packet) {
+                uart.write(&res)?;
+            }
+        }
+    }
+}
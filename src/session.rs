@@ -0,0 +1,43 @@
+use crate::crypto::{get_random_key, DEVICE_SIGNER};
+use crate::preludes::*;
+use dephy_message::delegation::create_delegation;
+use dephy_message::signer::{Signer, SoftwareSigner};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+struct Session {
+    signer: SoftwareSigner,
+    cert: DelegationCertificate,
+    not_after: u64,
+}
+
+lazy_static! {
+    static ref SESSION: Mutex<Option<Session>> = Mutex::new(None);
+}
+
+/// Session key certified by the device key for `APP_SESSION_KEY_LIFETIME` seconds.
+///
+/// The key is generated from the hardware RNG and lives in RAM only, a new one is certified
+/// when less than a tenth of the lifetime is left.
+pub fn current_session() -> Result<(SoftwareSigner, DelegationCertificate)> {
+    let now = Utc::now().timestamp() as u64;
+    let mut session = SESSION.lock();
+    let renew_at = |s: &Session| s.not_after.saturating_sub(APP_SESSION_KEY_LIFETIME / 10);
+    if session.as_ref().map_or(true, |s| now >= renew_at(s)) {
+        let signer = SoftwareSigner::new(get_random_key()?);
+        let not_after = now + APP_SESSION_KEY_LIFETIME;
+        let cert = create_delegation(&*DEVICE_SIGNER, &signer.verifying_key(), now, not_after)?;
+        info!(
+            "New session key: 0x{} valid until {}",
+            hex::encode(signer.address_bytes()),
+            not_after
+        );
+        *session = Some(Session {
+            signer,
+            cert,
+            not_after,
+        });
+    }
+    let session = session.as_ref().unwrap();
+    Ok((session.signer.clone(), session.cert.clone()))
+}
@@ -0,0 +1,101 @@
+use crate::crypto::{url_audience, DEVICE_SIGNER, MY_ADDRESS_BYTES};
+use crate::http::{request_response, request_text};
+use crate::preludes::*;
+use chrono::SecondsFormat;
+use dephy_message::siwe::{parse_siwe_nonce, parse_siwe_token, SiweMessage};
+use embedded_svc::http::Method;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+struct SiweSession {
+    /// Origin of `SIWE_LOGIN_URL`, the session is only sent to it.
+    origin: String,
+    header: (&'static str, String),
+    not_after: i64,
+}
+
+lazy_static! {
+    static ref SIWE_SESSION: Mutex<Option<SiweSession>> = Mutex::new(None);
+}
+
+/// Header carrying the SIWE session for requests to `url`, if the device is signed in to
+/// its origin.
+pub fn session_header(url: &str) -> Option<(&'static str, String)> {
+    let origin = url_audience(url).ok()?;
+    let now = Utc::now().timestamp();
+    SIWE_SESSION
+        .lock()
+        .as_ref()
+        .filter(|s| s.origin == origin && now < s.not_after)
+        .map(|s| s.header.clone())
+}
+
+/// Signs in to `SIWE_LOGIN_URL` unless the session is still valid.
+///
+/// The session is renewed when less than a tenth of `SIWE_SESSION_LIFETIME` is left.
+pub fn ensure_siwe_session() -> Result<()> {
+    if session_header(SIWE_LOGIN_URL).is_none() {
+        siwe_login()?;
+    }
+    Ok(())
+}
+
+/// Signs in with Ethereum (EIP-4361): fetches a nonce from `SIWE_NONCE_URL`, posts the
+/// message signed with the device key to `SIWE_LOGIN_URL`, and keeps the session cookie
+/// or token from the response for later requests.
+pub fn siwe_login() -> Result<()> {
+    *SIWE_SESSION.lock() = None;
+
+    let nonce = parse_siwe_nonce(&request_text(SIWE_NONCE_URL, Some(Method::Get), &[], None)?)?;
+    let origin = url_audience(SIWE_LOGIN_URL)?;
+    let domain = origin.split_once("://").map(|(_, d)| d).unwrap_or_default();
+    let now = Utc::now();
+    let expiration = now + chrono::Duration::seconds(SIWE_SESSION_LIFETIME as i64);
+    let mut msg = SiweMessage::new(
+        domain,
+        *MY_ADDRESS_BYTES,
+        SIWE_LOGIN_URL,
+        SIWE_CHAIN_ID,
+        nonce,
+        now.to_rfc3339_opts(SecondsFormat::Secs, true),
+    )
+    .expiration_time(expiration.to_rfc3339_opts(SecondsFormat::Secs, true));
+    if !SIWE_STATEMENT.is_empty() {
+        msg = msg.statement(SIWE_STATEMENT);
+    }
+    let body = msg.login_request(&*DEVICE_SIGNER)?;
+
+    let ret = request_response(
+        SIWE_LOGIN_URL,
+        Some(Method::Post),
+        &[("content-type", "application/json")],
+        Some(body.as_bytes()),
+    )?;
+    ensure!(
+        (200..300).contains(&ret.status),
+        "SIWE login failed: {} {}",
+        ret.status,
+        ret.body
+    );
+    let header = match (parse_siwe_token(&ret.body), ret.set_cookie) {
+        (Some(token), _) => ("authorization", format!("Bearer {}", token)),
+        (None, Some(cookie)) => (
+            "cookie",
+            cookie
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        ),
+        (None, None) => bail!("No session cookie or token in SIWE login response!"),
+    };
+
+    info!("Signed in to {} with SIWE", origin);
+    *SIWE_SESSION.lock() = Some(SiweSession {
+        origin,
+        header,
+        not_after: expiration.timestamp() - SIWE_SESSION_LIFETIME as i64 / 10,
+    });
+    Ok(())
+}
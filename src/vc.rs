@@ -0,0 +1,40 @@
+use crate::crypto::DEVICE_SIGNER;
+use crate::http::request_text;
+use crate::preludes::*;
+use chrono::TimeZone;
+use dephy_message::vc::CredentialBuilder;
+use embedded_svc::http::Method;
+
+/// JWT-VC issued by the device identity, attesting that `sensor` read `value` in `unit`
+/// at `observed_at`, the timestamp of the message envelope of the reading.
+pub fn issue_reading_credential(
+    sensor: &str,
+    value: f32,
+    unit: &str,
+    observed_at: u64,
+) -> Result<String> {
+    let observed_at = Utc
+        .timestamp_opt(observed_at as i64, 0)
+        .single()
+        .ok_or(anyhow!("Bad timestamp: {}", observed_at))?;
+    CredentialBuilder::new()
+        .credential_type("SensorReadingCredential")
+        .claim("sensor", sensor)
+        .claim("value", value)
+        .claim("unit", unit)
+        .claim("observedAt", observed_at.to_rfc3339())
+        .issued_at(Utc::now().timestamp() as u64)
+        .issue(&*DEVICE_SIGNER)
+}
+
+/// Posts `credential` to `VC_ENDPOINT_HTTP`.
+pub fn publish_credential(credential: &str) -> Result<()> {
+    let ret = request_text(
+        VC_ENDPOINT_HTTP,
+        Some(Method::Post),
+        &[("content-type", "application/jwt")],
+        Some(credential.as_bytes()),
+    )?;
+    debug!("Credential endpoint: {}", ret);
+    Ok(())
+}